@@ -0,0 +1,166 @@
+use std::{alloc::Layout, marker::PhantomData, ptr::NonNull};
+
+use crate::{Arena, ArenaError, assert_align};
+
+/// Allocation handle that holds the arena's `&'a mut` borrow and hands out
+/// references living for all of `'a`, not just one call.
+///
+/// `Arena::alloc` ties its result to the `&mut self` of that call, so a
+/// parser can't keep one node while allocating the next. Through an
+/// `ArenaRef` it can, and the arena still grows as needed: the handle owns
+/// the exclusive borrow, so nothing can reset or rewind the arena while any
+/// returned reference is alive.
+///
+/// The arena is only borrowed to reserve memory. Values are moved in, or
+/// dropped on failure, after that borrow ends, so a destructor calling back
+/// into the handle never finds the arena borrowed twice.
+pub struct ArenaRef<'a> {
+    arena: NonNull<Arena>,
+    _marker: PhantomData<&'a mut Arena>,
+}
+
+impl<'a> ArenaRef<'a> {
+    pub fn new(arena: &'a mut Arena) -> Self {
+        Self {
+            arena: NonNull::from(arena),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Exclusive access for the span of a single allocation.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn arena(&self) -> &mut Arena {
+        // references handed out point into block memory, never into the
+        // `Arena` itself, and no caller code runs while this is live
+        unsafe { &mut *self.arena.as_ptr() }
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    fn reserve<T>(&self) -> Result<*mut T, ArenaError> {
+        assert_align::<T>();
+        Ok(self.arena().try_alloc(Layout::new::<T>())? as *mut T)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc<T>(&self, obj: T) -> Result<&'a mut T, ArenaError> {
+        let ptr = self.reserve::<T>()?;
+        unsafe {
+            std::ptr::write(ptr, obj);
+            Ok(&mut *ptr)
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_with_drop<T: 'static>(&self, obj: T) -> Result<&'a mut T, ArenaError> {
+        let ptr = self.reserve::<T>()?;
+        unsafe {
+            std::ptr::write(ptr, obj);
+            self.arena().register_drop::<T>(ptr, 1);
+            Ok(&mut *ptr)
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_str(&self, str: &str) -> Result<&'a str, ArenaError> {
+        let ptr = self.arena().alloc_str(str)? as *const str;
+        Ok(unsafe { &*ptr })
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn copy_slice<T: Copy>(&self, slice: &[T]) -> Result<&'a mut [T], ArenaError> {
+        let ptr = self.arena().copy_slice(slice)? as *mut [T];
+        Ok(unsafe { &mut *ptr })
+    }
+
+    pub fn used(&self) -> usize {
+        self.arena().used()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Expr<'a> {
+        Num(i64),
+        Var(&'a str),
+        Add(&'a Expr<'a>, &'a Expr<'a>),
+    }
+
+    fn eval(expr: &Expr<'_>, x: i64) -> i64 {
+        match expr {
+            Expr::Num(n) => *n,
+            Expr::Var(name) => {
+                assert_eq!(*name, "x");
+                x
+            }
+            Expr::Add(lhs, rhs) => eval(lhs, x) + eval(rhs, x),
+        }
+    }
+
+    fn parse<'a>(arena: &ArenaRef<'a>, src: &str) -> &'a Expr<'a> {
+        let mut terms = src.split('+').map(str::trim).map(|term| {
+            let expr = match term.parse() {
+                Ok(n) => Expr::Num(n),
+                Err(_) => Expr::Var(arena.alloc_str(term).unwrap()),
+            };
+            &*arena.alloc(expr).unwrap()
+        });
+
+        let first = terms.next().unwrap();
+        terms.fold(first, |lhs, rhs| arena.alloc(Expr::Add(lhs, rhs)).unwrap())
+    }
+
+    #[test]
+    fn test_ast_outlives_alloc_calls() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        let (a, b, used) = {
+            let arena = ArenaRef::new(&mut arena);
+            let a = parse(&arena, "1 + x + 40");
+            let b = parse(&arena, "x + x + 2 + 3");
+            (eval(a, 1), eval(b, 5), arena.used())
+        };
+
+        assert_eq!((a, b), (42, 15));
+        assert_eq!(arena.used(), used);
+        assert!(arena.blocks.len() > 1);
+    }
+
+    #[test]
+    fn test_failed_alloc_drops_value_after_borrow() {
+        struct Probe<'r, 'a>(&'r ArenaRef<'a>, &'r std::cell::Cell<usize>);
+
+        impl Drop for Probe<'_, '_> {
+            fn drop(&mut self) {
+                self.1.set(self.0.used());
+            }
+        }
+
+        let mut arena = Arena::fixed(64).unwrap();
+        let arena = ArenaRef::new(&mut arena);
+        arena.alloc([0u8; 64]).unwrap();
+
+        let seen = std::cell::Cell::new(0);
+        assert!(arena.alloc(Probe(&arena, &seen)).is_err());
+        assert_eq!(seen.get(), 64);
+    }
+
+    #[test]
+    fn test_nodes_survive_growth() {
+        let mut arena = Arena::with_block_size(32).unwrap();
+        let arena = ArenaRef::new(&mut arena);
+
+        let nodes: Vec<&mut u64> = (0..64).map(|i| arena.alloc(i).unwrap()).collect();
+        let names = arena.copy_slice(&[1u16, 2, 3]).unwrap();
+        for (i, node) in nodes.iter().enumerate() {
+            assert_eq!(**node, i as u64);
+        }
+        assert_eq!(names, &[1, 2, 3]);
+    }
+}
@@ -0,0 +1,141 @@
+use std::ptr::NonNull;
+
+use crate::{Arena, ArenaError};
+
+/// Index of a node inside the `GraphBuilder` that created it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeHandle(u32);
+
+pub struct Node<T> {
+    value: T,
+    edges: Vec<NodeHandle>,
+}
+
+impl<T> Node<T> {
+    #[inline]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    #[inline]
+    pub fn edges(&self) -> &[NodeHandle] {
+        &self.edges
+    }
+}
+
+/// Builds arena-allocated graphs, cycles included. Nodes are linked by
+/// handle, references are only formed when reading.
+pub struct GraphBuilder<'a, T> {
+    arena: &'a mut Arena,
+    nodes: Vec<NonNull<Node<T>>>,
+}
+
+impl<'a, T> GraphBuilder<'a, T> {
+    pub fn new(arena: &'a mut Arena) -> Self {
+        Self {
+            arena,
+            nodes: Vec::new(),
+        }
+    }
+
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn add_node(&mut self, value: T) -> Result<NodeHandle, ArenaError>
+    where
+        T: 'static,
+    {
+        let handle = u32::try_from(self.nodes.len())
+            .map(NodeHandle)
+            .map_err(|_| ArenaError::CapacityExceeded)?;
+
+        let node = self.arena.alloc_with_drop(Node {
+            value,
+            edges: Vec::new(),
+        })?;
+        self.nodes.push(NonNull::from(node));
+
+        Ok(handle)
+    }
+
+    /// Adds a directed edge, panics if either handle isn't from this builder.
+    pub fn add_edge(&mut self, from: NodeHandle, to: NodeHandle) {
+        assert!((to.0 as usize) < self.nodes.len(), "invalid node handle");
+        self.node_mut(from).edges.push(to);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, handle: NodeHandle) -> Option<&Node<T>> {
+        self.nodes
+            .get(handle.0 as usize)
+            .map(|node| unsafe { node.as_ref() })
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, handle: NodeHandle) -> Option<&mut T> {
+        self.nodes
+            .get_mut(handle.0 as usize)
+            .map(|node| unsafe { &mut node.as_mut().value })
+    }
+
+    pub fn neighbors(&self, handle: NodeHandle) -> impl Iterator<Item = NodeHandle> + '_ {
+        self.get(handle)
+            .into_iter()
+            .flat_map(|node| node.edges.iter().copied())
+    }
+
+    #[inline]
+    fn node_mut(&mut self, handle: NodeHandle) -> &mut Node<T> {
+        let node = self
+            .nodes
+            .get_mut(handle.0 as usize)
+            .expect("invalid node handle");
+        unsafe { node.as_mut() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_builder_cycle() {
+        let mut arena = Arena::new().unwrap();
+        let mut graph = GraphBuilder::new(&mut arena);
+
+        let a = graph.add_node("a").unwrap();
+        let b = graph.add_node("b").unwrap();
+        graph.add_edge(a, b);
+        graph.add_edge(b, a);
+
+        let mut curr = a;
+        let mut visited = Vec::new();
+        for _ in 0..4 {
+            visited.push(*graph.get(curr).unwrap().value());
+            curr = graph.neighbors(curr).next().unwrap();
+        }
+
+        assert_eq!(visited, ["a", "b", "a", "b"]);
+        assert_eq!(graph.get(b).unwrap().edges(), &[a]);
+    }
+
+    #[test]
+    fn test_graph_builder_mutates_through_handle() {
+        let mut arena = Arena::new().unwrap();
+        let mut graph = GraphBuilder::new(&mut arena);
+
+        let node = graph.add_node(1).unwrap();
+        *graph.get_mut(node).unwrap() += 41;
+
+        assert_eq!(graph.get(node).unwrap().value(), &42);
+        assert!(graph.get(NodeHandle(7)).is_none());
+    }
+}
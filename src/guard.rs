@@ -0,0 +1,98 @@
+use std::ptr::NonNull;
+
+/// Usable (page-rounded) size and total mapping length, guard page included.
+#[inline]
+pub(crate) fn mapping_len(size: usize, page: usize) -> Option<(usize, usize)> {
+    let usable = size.checked_next_multiple_of(page)?;
+    let total = usable.checked_add(page)?;
+    Some((usable, total))
+}
+
+#[inline]
+pub(crate) fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Maps `size` bytes rounded up to whole pages, followed by one `PROT_NONE`
+/// page. Returns the start and the usable length.
+pub(crate) unsafe fn map(size: usize) -> Option<(NonNull<u8>, usize)> {
+    let page = page_size();
+    let (usable, total) = mapping_len(size, page)?;
+
+    unsafe {
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            total,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+
+        let guard = (ptr as *mut u8).add(usable);
+        if libc::mprotect(guard as *mut libc::c_void, page, libc::PROT_NONE) != 0 {
+            libc::munmap(ptr, total);
+            return None;
+        }
+
+        Some((NonNull::new_unchecked(ptr as *mut u8), usable))
+    }
+}
+
+/// Unmaps a region returned by `map`, guard page included.
+pub(crate) unsafe fn unmap(ptr: NonNull<u8>, usable: usize) {
+    let page = page_size();
+    unsafe { libc::munmap(ptr.as_ptr() as *mut libc::c_void, usable + page) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Arena;
+
+    #[test]
+    fn test_mapping_len() {
+        assert_eq!(mapping_len(1, 4096), Some((4096, 8192)));
+        assert_eq!(mapping_len(4096, 4096), Some((4096, 8192)));
+        assert_eq!(mapping_len(100_000, 4096), Some((102_400, 106_496)));
+        assert_eq!(mapping_len(usize::MAX, 4096), None);
+    }
+
+    #[test]
+    fn test_guarded_block_rounds_to_page() {
+        let mut arena = Arena::with_guard_pages(100).unwrap();
+        let block = arena.blocks[0].get_mut();
+
+        assert_eq!(block.size, page_size());
+        assert_eq!(block.start_ptr.as_ptr() as usize % page_size(), 0);
+
+        let bytes = arena.alloc([7u8; 64]).unwrap();
+        assert!(bytes.iter().all(|&b| b == 7));
+    }
+
+    /// Writing one byte past a guarded block must kill the writer with
+    /// SIGSEGV, checked in a forked child so the test runner survives.
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_write_past_block_faults() {
+        let mut arena = Arena::with_guard_pages(page_size()).unwrap();
+        let end = arena.blocks[0].get_mut().end_ptr.as_ptr();
+
+        unsafe {
+            let pid = libc::fork();
+            assert!(pid >= 0);
+            if pid == 0 {
+                std::ptr::write_volatile(end, 1);
+                libc::_exit(0);
+            }
+
+            let mut status = 0;
+            libc::waitpid(pid, &mut status, 0);
+            assert!(libc::WIFSIGNALED(status));
+            assert_eq!(libc::WTERMSIG(status), libc::SIGSEGV);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    alloc::Layout,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use crate::{Arena, ArenaError, ScopedArena};
+
+/// Vector keeping its first `N` elements inline, it only touches the arena
+/// once it grows past `N`.
+pub struct InlineArenaVec<'a, T, const N: usize> {
+    arena: &'a mut Arena,
+    inline: [MaybeUninit<T>; N],
+
+    /// arena backing once spilled
+    spilled: Option<NonNull<T>>,
+    len: usize,
+    capacity: usize,
+}
+
+impl<'a, T, const N: usize> InlineArenaVec<'a, T, N> {
+    pub fn new(arena: &'a mut Arena) -> Self {
+        let capacity = if size_of::<T>() == 0 { usize::MAX } else { N };
+
+        Self {
+            arena,
+            inline: [const { MaybeUninit::uninit() }; N],
+            spilled: None,
+            len: 0,
+            capacity,
+        }
+    }
+
+    /// Vector in `scope`, whose spilled storage is reclaimed when the scope
+    /// ends.
+    pub fn in_scope(scope: &'a mut ScopedArena<'_>) -> Self {
+        Self::new(scope.arena_mut())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    pub fn is_spilled(&self) -> bool {
+        self.spilled.is_some()
+    }
+
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn push(&mut self, value: T) -> Result<(), ArenaError> {
+        if self.len == self.capacity {
+            self.grow()?;
+        }
+
+        unsafe { self.as_mut_ptr().add(self.len).write(value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        Some(unsafe { self.as_mut_ptr().add(self.len).read() })
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const T {
+        match self.spilled {
+            Some(ptr) => ptr.as_ptr(),
+            None => self.inline.as_ptr() as *const T,
+        }
+    }
+
+    #[inline]
+    fn as_mut_ptr(&mut self) -> *mut T {
+        match self.spilled {
+            Some(ptr) => ptr.as_ptr(),
+            None => self.inline.as_mut_ptr() as *mut T,
+        }
+    }
+
+    #[cfg_attr(feature = "debug", track_caller)]
+    fn grow(&mut self) -> Result<(), ArenaError> {
+        let new_capacity = self
+            .capacity
+            .checked_mul(2)
+            .ok_or(ArenaError::InsufficientMemory)?
+            .max(4);
+        let old_size = self.capacity * size_of::<T>();
+        let new_layout = Layout::array::<T>(new_capacity)?;
+
+        if let Some(ptr) = self.spilled {
+            let ptr = ptr.as_ptr() as *mut u8;
+            if self.arena.grow_last(ptr, old_size, new_layout.size()) {
+                self.capacity = new_capacity;
+                return Ok(());
+            }
+        }
+
+        let new_ptr = self.arena.try_alloc(new_layout)? as *mut T;
+        unsafe { std::ptr::copy_nonoverlapping(self.as_ptr(), new_ptr, self.len) };
+
+        self.spilled = Some(unsafe { NonNull::new_unchecked(new_ptr) });
+        self.capacity = new_capacity;
+        Ok(())
+    }
+}
+
+impl<T, const N: usize> Deref for InlineArenaVec<'_, T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for InlineArenaVec<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, const N: usize> Drop for InlineArenaVec<'_, T, N> {
+    fn drop(&mut self) {
+        unsafe { std::ptr::drop_in_place(self.as_mut_slice()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_vec_stays_inline() {
+        let mut arena = Arena::with_block_size(256).unwrap();
+        let before = arena.largest_available();
+
+        {
+            let mut vec = InlineArenaVec::<u32, 4>::new(&mut arena);
+            for i in 0..4 {
+                vec.push(i).unwrap();
+            }
+
+            assert!(!vec.is_spilled());
+            assert_eq!(&vec[..], &[0, 1, 2, 3]);
+        }
+
+        assert_eq!(arena.largest_available(), before);
+    }
+
+    #[test]
+    fn test_inline_vec_spills_to_arena() {
+        let mut arena = Arena::with_block_size(256).unwrap();
+        let before = arena.largest_available();
+
+        {
+            let mut vec = InlineArenaVec::<u32, 4>::new(&mut arena);
+            for i in 0..20 {
+                vec.push(i).unwrap();
+            }
+
+            assert!(vec.is_spilled());
+            assert_eq!(vec.len(), 20);
+            assert_eq!(vec.pop(), Some(19));
+            assert!(vec.iter().copied().eq(0..19));
+        }
+
+        assert!(arena.largest_available() < before);
+    }
+
+    #[test]
+    fn test_inline_vec_drops_elements() {
+        let mut arena = Arena::new().unwrap();
+        let counter = std::rc::Rc::new(());
+
+        {
+            let mut vec = InlineArenaVec::<_, 2>::new(&mut arena);
+            for _ in 0..5 {
+                vec.push(counter.clone()).unwrap();
+            }
+            assert_eq!(std::rc::Rc::strong_count(&counter), 6);
+        }
+
+        assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+    }
+}
@@ -1,35 +1,190 @@
+mod arena_ref;
+mod graph;
+#[cfg(all(feature = "guard-pages", unix))]
+mod guard;
+mod inline_vec;
+mod node_pool;
+mod scoped;
+mod typed_alloc;
+#[cfg(feature = "typed-store")]
+mod typed_store;
+
+pub use arena_ref::ArenaRef;
+pub use graph::{GraphBuilder, Node, NodeHandle};
+pub use inline_vec::InlineArenaVec;
+pub use node_pool::NodePool;
+pub use scoped::ScopedArena;
+pub use typed_alloc::TypedAllocator;
+#[cfg(feature = "typed-store")]
+pub use typed_store::TypedArena;
+
 use std::{
     alloc::{Layout, LayoutError, alloc, dealloc},
     cell::{Cell, UnsafeCell},
     fmt::Display,
+    mem::MaybeUninit,
     ptr::NonNull,
 };
 
 #[repr(C)]
 pub struct Arena {
     blocks: Vec<UnsafeCell<Block>>,
-    block_size: BlockSize,
+    config: ArenaConfig,
+
+    /// process-wide unique, ties snapshots to the arena that took them
+    uid: u64,
+
+    /// pending destructors, in registration order
+    drops: Vec<DropThunk>,
+
+    /// destructors ever registered, numbers the next one
+    drop_seq: u64,
+
+    /// id handed to the next block, ids are never reused
+    next_block_id: BlockId,
+
+    /// index of the block that served the latest allocation
+    active: usize,
+
+    /// heap allocations made under `OnOverflow::Spill`, in allocation order
+    spills: Vec<(NonNull<u8>, Layout)>,
+
+    /// blocks and spills ever requested from the system
+    system_allocs: u64,
+
+    /// every live allocation, in allocation order
+    #[cfg(feature = "stats")]
+    allocations: Vec<AllocRecord>,
+
+    /// phase markers with the `used` bytes at the time they were set
+    #[cfg(feature = "stats")]
+    phases: Vec<(String, usize)>,
+
+    /// called with the duration of every reset
+    #[cfg(feature = "timing")]
+    reset_hook: Option<Box<dyn FnMut(std::time::Duration)>>,
 }
 
 impl Arena {
     pub fn new() -> Result<Self, ArenaError> {
-        let block_size = DEFAULT_BLOCK_SIZE;
-        Self::with_block_size(block_size)
+        Self::with_config(ArenaConfig::default())
     }
 
     pub fn with_block_size(size: usize) -> Result<Self, ArenaError> {
-        let block = Block::new(size)?;
+        Self::with_config(ArenaConfig::new().block_size(size))
+    }
+
+    /// Arena that never grows past its first block of `size` bytes.
+    pub fn fixed(size: usize) -> Result<Self, ArenaError> {
+        Self::with_config(ArenaConfig::new().block_size(size).fixed(true))
+    }
+
+    /// Arena whose blocks are each followed by an unmapped page, so writing
+    /// past a block faults instead of corrupting memory.
+    #[cfg(all(feature = "guard-pages", unix))]
+    pub fn with_guard_pages(block_size: usize) -> Result<Self, ArenaError> {
+        Self::with_config(ArenaConfig::new().block_size(block_size).guard_pages(true))
+    }
+
+    pub fn with_config(config: ArenaConfig) -> Result<Self, ArenaError> {
+        let block = Block::with_config(config.block_size, BLOCK_ALIGN, &config)?;
 
         Ok(Self {
             blocks: vec![UnsafeCell::new(block)],
-            block_size: size,
+            config,
+            uid: next_arena_uid(),
+            drops: Vec::new(),
+            drop_seq: 0,
+            next_block_id: 1,
+            active: 0,
+            spills: Vec::new(),
+            system_allocs: 1,
+            #[cfg(feature = "stats")]
+            allocations: Vec::new(),
+            #[cfg(feature = "stats")]
+            phases: Vec::new(),
+            #[cfg(feature = "timing")]
+            reset_hook: None,
         })
     }
 
+    /// Splits the arena into its blocks and config. Pending destructors run
+    /// first, the bytes stay in the blocks.
+    pub fn into_parts(mut self) -> (Vec<OwnedBlock>, ArenaConfig) {
+        self.run_drops();
+        self.free_spills(0);
+
+        let blocks = std::mem::take(&mut self.blocks)
+            .into_iter()
+            .map(|block| OwnedBlock(block.into_inner()))
+            .collect();
+
+        (blocks, self.config)
+    }
+
+    /// Rebuilds an arena from `into_parts` output. Blocks keep their ids and
+    /// the result is checked with `verify`.
+    pub fn from_parts(blocks: Vec<OwnedBlock>, config: ArenaConfig) -> Result<Self, ArenaError> {
+        // ids are kept, snapshots taken before `into_parts` must never match
+        // a block created afterwards
+        let next_block_id = blocks.iter().map(|block| block.id() + 1).max().unwrap_or(0);
+        let blocks: Vec<_> = blocks
+            .into_iter()
+            .map(|OwnedBlock(block)| UnsafeCell::new(block))
+            .collect();
+
+        let arena = Self {
+            next_block_id,
+            active: blocks.len().saturating_sub(1),
+            spills: Vec::new(),
+            system_allocs: 0,
+            blocks,
+            config,
+            uid: next_arena_uid(),
+            drops: Vec::new(),
+            drop_seq: 0,
+            #[cfg(feature = "stats")]
+            allocations: Vec::new(),
+            #[cfg(feature = "stats")]
+            phases: Vec::new(),
+            #[cfg(feature = "timing")]
+            reset_hook: None,
+        };
+
+        arena.verify()?;
+        Ok(arena)
+    }
+
+    /// Checks the structural invariants: at least one block, unique ids
+    /// below `next_block_id`, every cursor inside its block.
+    pub fn verify(&self) -> Result<(), ArenaError> {
+        if self.blocks.is_empty() || self.active >= self.blocks.len() {
+            return Err(ArenaError::Corrupted);
+        }
+
+        let mut ids = std::collections::HashSet::new();
+        for block in &self.blocks {
+            let block = unsafe { &*block.get() };
+            let curr = block.curr_ptr.get();
+
+            let in_bounds = block.start_ptr.as_ptr() <= curr && curr <= block.end_ptr.as_ptr();
+            let sized =
+                block.end_ptr.as_ptr() as usize - block.start_ptr.as_ptr() as usize == block.size;
+            if !in_bounds || !sized || block.id >= self.next_block_id || !ids.insert(block.id) {
+                return Err(ArenaError::Corrupted);
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn scope<Func, FuncResult>(&mut self, func: Func) -> FuncResult
     where
         Func: FnOnce(&mut Arena) -> FuncResult,
     {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("arena_scope").entered();
+
         let snapshot = self.snapshot();
         let result = func(self);
         self.rewind_to(snapshot);
@@ -37,8 +192,32 @@ impl Arena {
         result
     }
 
+    /// Handle whose allocations live as long as this borrow of the arena,
+    /// see `ArenaRef`.
+    #[inline]
+    pub fn by_ref(&mut self) -> ArenaRef<'_> {
+        ArenaRef::new(self)
+    }
+
+    /// Guard version of `scope`: the arena is rewound when the returned
+    /// `ScopedArena` is dropped.
     #[inline]
+    pub fn scoped(&mut self) -> ScopedArena<'_> {
+        ScopedArena::new(self)
+    }
+
+    /// Handle for allocating many values of one type, see `TypedAllocator`.
+    #[inline]
+    pub fn typed<T>(&mut self) -> TypedAllocator<'_, T> {
+        TypedAllocator::new(self)
+    }
+
+    /// Moves `obj` into the arena. It's never dropped, use `alloc_with_drop`
+    /// for values that own resources.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
     pub fn alloc<T: Sized>(&mut self, obj: T) -> Result<&mut T, ArenaError> {
+        assert_align::<T>();
         let layout = Layout::new::<T>();
         let ptr = self.try_alloc(layout)? as *mut T;
         unsafe {
@@ -47,272 +226,3847 @@ impl Arena {
         }
     }
 
+    /// Allocates through a shared reference, so several helpers holding
+    /// `&Arena` can allocate side by side.
+    ///
+    /// Only room in existing blocks is used: instead of growing this fails
+    /// with `ArenaError::InsufficientMemory`, `try_reserve` beforehand makes
+    /// room. `obj` is never dropped and isn't tracked by `stats`. `reset`,
+    /// `rewind_to` and friends take `&mut self`, so they can't run while the
+    /// returned reference is alive.
     #[inline]
-    pub fn alloc_slice<T: Sized>(&mut self, length: usize) -> Result<&mut [T], ArenaError> {
-        let layout = Layout::array::<T>(length)?;
+    pub fn alloc_shared<T: Sized>(&self, obj: T) -> Result<&T, ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::new::<T>();
+        let ptr = self.try_alloc_shared(layout)? as *mut T;
+        unsafe {
+            std::ptr::write(ptr, obj);
+            Ok(&*ptr)
+        }
+    }
+
+    /// Slice counterpart of `alloc_shared`, copying `slice`.
+    #[inline]
+    pub fn copy_slice_shared<T: Copy>(&self, slice: &[T]) -> Result<&[T], ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::array::<T>(slice.len())?;
+        let ptr = self.try_alloc_shared(layout)? as *mut T;
+        unsafe {
+            std::ptr::copy_nonoverlapping(slice.as_ptr(), ptr, slice.len());
+            Ok(&*std::ptr::slice_from_raw_parts(ptr, slice.len()))
+        }
+    }
+
+    /// String counterpart of `alloc_shared`.
+    ///
+    /// Shared allocations can borrow each other, which is how a value
+    /// referencing earlier arena data is built: reserve room with `&mut`
+    /// once, then allocate through `&self`.
+    ///
+    /// ```
+    /// use arena::Arena;
+    ///
+    /// struct Node<'a> {
+    ///     name: &'a str,
+    ///     parent: Option<&'a Node<'a>>,
+    /// }
+    ///
+    /// let mut arena = Arena::new().unwrap();
+    /// arena.try_reserve(256).unwrap();
+    ///
+    /// let arena = &arena;
+    /// let name = arena.alloc_str_shared("root").unwrap();
+    /// let root = arena.alloc_shared(Node { name, parent: None }).unwrap();
+    /// let name = arena.alloc_str_shared("child").unwrap();
+    /// let child = arena.alloc_shared(Node { name, parent: Some(root) }).unwrap();
+    ///
+    /// assert_eq!(child.parent.unwrap().name, "root");
+    /// ```
+    #[inline]
+    pub fn alloc_str_shared(&self, str: &str) -> Result<&str, ArenaError> {
+        let bytes = self.copy_slice_shared(str.as_bytes())?;
+        Ok(unsafe { std::str::from_utf8_unchecked(bytes) })
+    }
+
+    /// Like `alloc`, but returns a raw pointer so no `&mut T` is formed.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_ptr<T: Sized>(&mut self, obj: T) -> Result<NonNull<T>, ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::new::<T>();
         let ptr = self.try_alloc(layout)? as *mut T;
         unsafe {
-            std::ptr::write_bytes(ptr, 0, length);
-            Ok(&mut *std::ptr::slice_from_raw_parts_mut(ptr, length))
+            std::ptr::write(ptr, obj);
+            Ok(NonNull::new_unchecked(ptr))
         }
     }
 
+    /// Raw counterpart of `alloc_slice`: the elements are uninitialized
+    /// unless `zero_slices` is set.
     #[inline]
-    pub fn alloc_str(&mut self, str: &str) -> Result<&str, ArenaError> {
-        let copied = self.copy_slice(str.as_bytes())?;
-        let slice = unsafe { std::str::from_utf8_unchecked(copied) };
-        Ok(slice)
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_slice_ptr<T: Sized>(&mut self, length: usize) -> Result<NonNull<[T]>, ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::array::<T>(length)?;
+        let ptr = self.try_alloc(layout)? as *mut T;
+        unsafe {
+            if self.config.zero_slices {
+                std::ptr::write_bytes(ptr, 0, length);
+            }
+            Ok(NonNull::slice_from_raw_parts(
+                NonNull::new_unchecked(ptr),
+                length,
+            ))
+        }
     }
 
+    /// Like `alloc`, but `obj` is dropped when the arena is reset, rewound
+    /// past it or dropped. `T: 'static` keeps the destructor from running
+    /// after data it borrows is gone:
+    ///
+    /// ```compile_fail
+    /// let mut arena = arena::Arena::new().unwrap();
+    /// {
+    ///     let name = String::from("gone");
+    ///     arena.alloc_with_drop(name.as_str()).unwrap();
+    /// }
+    /// arena.reset();
+    /// ```
     #[inline]
-    pub fn copy_slice<T: Copy>(&mut self, slice: &[T]) -> Result<&mut [T], ArenaError> {
-        let layout = Layout::array::<T>(slice.len())?;
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_with_drop<T: 'static>(&mut self, obj: T) -> Result<&mut T, ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::new::<T>();
         let ptr = self.try_alloc(layout)? as *mut T;
         unsafe {
-            std::ptr::copy_nonoverlapping(slice.as_ptr(), ptr, slice.len());
-            Ok(&mut *std::ptr::slice_from_raw_parts_mut(ptr, slice.len()))
+            std::ptr::write(ptr, obj);
+            self.register_drop::<T>(ptr, 1);
+            Ok(&mut *ptr)
         }
     }
 
+    /// Correctly sized and aligned uninitialized space for a `T`.
     #[inline]
-    pub fn reset(&mut self) {
-        for block in &mut self.blocks {
-            block.get_mut().reset();
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_uninit<T: Sized>(&mut self) -> Result<&mut MaybeUninit<T>, ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::new::<T>();
+        let ptr = self.try_alloc(layout)? as *mut MaybeUninit<T>;
+        Ok(unsafe { &mut *ptr })
+    }
+
+    /// Allocates a zero-initialized `T`.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_zeroed<T: Zeroable>(&mut self) -> Result<&mut T, ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::new::<T>();
+        let ptr = self.try_alloc(layout)? as *mut T;
+        unsafe {
+            std::ptr::write_bytes(ptr, 0, 1);
+            Ok(&mut *ptr)
         }
     }
 
+    /// Allocates `length` zero-initialized elements, regardless of the
+    /// `zero_slices` setting.
     #[inline]
-    pub fn reset_zeroed(&mut self) {
-        for block in &mut self.blocks {
-            block.get_mut().reset_zeroed();
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_slice_zeroed<T: Zeroable>(
+        &mut self,
+        length: usize,
+    ) -> Result<&mut [T], ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::array::<T>(length)?;
+        let ptr = self.try_alloc(layout)? as *mut T;
+        unsafe {
+            std::ptr::write_bytes(ptr, 0, length);
+            Ok(&mut *std::ptr::slice_from_raw_parts_mut(ptr, length))
         }
     }
 
+    /// Space for a (typically `repr(C)`) union. Unions can't be zeroed
+    /// safely in general, so the memory is left uninitialized for the
+    /// caller to write one variant into.
     #[inline]
-    fn try_alloc(&mut self, layout: Layout) -> Result<*mut u8, ArenaError> {
-        let block = match self.try_get_block(layout) {
-            Some(block) => block,
-            None => self.alloc_new_block(layout.size())?,
-        };
-        block.alloc(layout)
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_union<T: Sized>(&mut self) -> Result<&mut MaybeUninit<T>, ArenaError> {
+        self.alloc_uninit::<T>()
     }
 
+    /// Allocates `obj` and hands it out with a `'static` lifetime.
+    ///
+    /// # Safety
+    /// The arena must outlive every use of the returned reference: it must
+    /// never be dropped, reset or rewound past this allocation, e.g. because
+    /// it was leaked with `Box::leak`.
     #[inline]
-    fn alloc_new_block(&mut self, size: BlockSize) -> Result<&mut Block, ArenaError> {
-        let block = Block::new(self.block_size.max(size))?;
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub unsafe fn leak<T: Sized>(&mut self, obj: T) -> Result<&'static mut T, ArenaError> {
+        let ptr = self.alloc(obj)? as *mut T;
+        Ok(unsafe { &mut *ptr })
+    }
 
-        self.blocks.push(UnsafeCell::new(block));
-        Ok(self.blocks.last_mut().unwrap().get_mut())
+    /// Allocates an atomic, which is always placed at its natural alignment
+    /// since a misaligned atomic access is undefined behaviour.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_atomic<T: Atomic>(&mut self, obj: T) -> Result<&mut T, ArenaError> {
+        let obj = self.alloc(obj)?;
+        debug_assert!((obj as *mut T).is_aligned());
+        Ok(obj)
     }
 
+    /// Zero-initialized slice, hence the `Zeroable` bound. See
+    /// `alloc_uninit_slice` to skip the memset.
     #[inline]
-    fn try_get_block(&mut self, layout: Layout) -> Option<&mut Block> {
-        for block in &mut self.blocks {
-            let deref_block = block.get_mut();
-            if deref_block.remaining() > layout.size() {
-                return Some(deref_block);
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_slice<T: Zeroable>(&mut self, length: usize) -> Result<&mut [T], ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::array::<T>(length)?;
+        let ptr = self.try_alloc(layout)? as *mut T;
+        unsafe {
+            if self.config.zero_slices {
+                std::ptr::write_bytes(ptr, 0, length);
             }
+            Ok(&mut *std::ptr::slice_from_raw_parts_mut(ptr, length))
         }
-        None
     }
 
-    pub fn snapshot(&self) -> ArenaSnapshot {
-        let block_idx = self.blocks.len() - 1;
-        let block = unsafe { &*self.blocks[block_idx].get() };
-        let offset = block.curr_ptr.get();
+    /// Uninitialized slice for the caller to fill, no memset and no bound on
+    /// `T`. Nothing is allocated for `length == 0`.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_uninit_slice<T: Sized>(
+        &mut self,
+        length: usize,
+    ) -> Result<&mut [MaybeUninit<T>], ArenaError> {
+        if length == 0 {
+            return Ok(&mut []);
+        }
 
-        ArenaSnapshot { block_idx, offset }
+        assert_align::<T>();
+        let layout = Layout::array::<T>(length)?;
+        let ptr = self.try_alloc(layout)? as *mut MaybeUninit<T>;
+        Ok(unsafe { &mut *std::ptr::slice_from_raw_parts_mut(ptr, length) })
     }
 
+    /// Slice of `length` clones of `value`. Elements needing `Drop` are
+    /// dropped when the arena is reset, rewound past them or dropped.
     #[inline]
-    pub fn rewind_to(&mut self, snapshot: ArenaSnapshot) {
-        if let Some(block) = self.blocks.get_mut(snapshot.block_idx) {
-            let block = block.get_mut();
-            block.rewind_to(snapshot.offset);
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_slice_fill<T: Clone + 'static>(
+        &mut self,
+        length: usize,
+        value: T,
+    ) -> Result<&mut [T], ArenaError> {
+        self.alloc_slice_from_fn(length, |_| value.clone())
+    }
+
+    /// Interior-mutable node: the returned `&Cell` can be copied around and
+    /// written through. `value` is dropped like with `alloc_with_drop`.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_cell<T: 'static>(&mut self, value: T) -> Result<&Cell<T>, ArenaError> {
+        self.alloc_with_drop(Cell::new(value)).map(|cell| &*cell)
+    }
+
+    /// `alloc_cell` for values that need borrowing rather than copying.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_refcell<T: 'static>(
+        &mut self,
+        value: T,
+    ) -> Result<&std::cell::RefCell<T>, ArenaError> {
+        self.alloc_with_drop(std::cell::RefCell::new(value))
+            .map(|cell| &*cell)
+    }
+
+    /// Slice of `Cell`s initialized to `T::default()`, elements can be
+    /// updated through shared references. Dropped like `alloc_slice_from_fn`.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_slice_cell<T: Default + 'static>(
+        &mut self,
+        length: usize,
+    ) -> Result<&[Cell<T>], ArenaError> {
+        assert_align::<Cell<T>>();
+        let layout = Layout::array::<Cell<T>>(length)?;
+        let ptr = self.try_alloc(layout)? as *mut Cell<T>;
+        unsafe {
+            for i in 0..length {
+                std::ptr::write(ptr.add(i), Cell::new(T::default()));
+            }
+            self.register_drop::<Cell<T>>(ptr, length);
+            Ok(&*std::ptr::slice_from_raw_parts(ptr, length))
         }
+    }
 
-        for block in self.blocks.iter_mut().skip(snapshot.block_idx + 1) {
-            block.get_mut().reset();
+    /// Slice whose `i`-th element is `f(i)`. Elements needing `Drop` are
+    /// dropped when the arena is reset, rewound past them or dropped.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_slice_from_fn<T, F>(
+        &mut self,
+        length: usize,
+        mut f: F,
+    ) -> Result<&mut [T], ArenaError>
+    where
+        T: 'static,
+        F: FnMut(usize) -> T,
+    {
+        assert_align::<T>();
+        let layout = Layout::array::<T>(length)?;
+        let ptr = self.try_alloc(layout)? as *mut T;
+        unsafe {
+            for i in 0..length {
+                std::ptr::write(ptr.add(i), f(i));
+            }
+            self.register_drop::<T>(ptr, length);
+            Ok(&mut *std::ptr::slice_from_raw_parts_mut(ptr, length))
         }
     }
 
-    #[cfg(feature = "debug")]
-    pub fn dump(&self) {
-        println!("Arena Debug Dump");
-        println!("================");
-        println!("Total blocks: {}", self.blocks.len());
+    /// Slice holding the items of `iter`, dropped like `alloc_slice_from_fn`.
+    /// An iterator yielding fewer items than it reported gives a shorter slice.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_slice_from_iter<T, I>(&mut self, iter: I) -> Result<&mut [T], ArenaError>
+    where
+        T: 'static,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iter = iter.into_iter();
+        let capacity = iter.len();
+        assert_align::<T>();
+        let layout = Layout::array::<T>(capacity)?;
+        let ptr = self.try_alloc(layout)? as *mut T;
 
-        for (i, block) in self.blocks.iter().enumerate() {
-            unsafe { &*block.get() }.dump(i);
+        let mut length = 0;
+        unsafe {
+            for item in iter.take(capacity) {
+                std::ptr::write(ptr.add(length), item);
+                length += 1;
+            }
+            self.register_drop::<T>(ptr, length);
+            Ok(&mut *std::ptr::slice_from_raw_parts_mut(ptr, length))
         }
+    }
 
-        println!();
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_str(&mut self, str: &str) -> Result<&str, ArenaError> {
+        let copied = self.copy_slice(str.as_bytes())?;
+        let slice = unsafe { std::str::from_utf8_unchecked(copied) };
+        Ok(slice)
     }
-}
 
-#[must_use]
-pub struct ArenaSnapshot {
-    block_idx: usize,
+    /// Copies `bytes` as a string after checking they're valid UTF-8.
+    /// Nothing is allocated when they aren't.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_str_from_bytes(&mut self, bytes: &[u8]) -> Result<&str, ArenaError> {
+        let str = std::str::from_utf8(bytes).map_err(|err| ArenaError::InvalidUtf8 {
+            valid_up_to: err.valid_up_to(),
+        })?;
+        self.alloc_str(str)
+    }
 
-    /// block's save point
-    offset: *mut u8,
-}
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_str_concat(&mut self, parts: &[&str]) -> Result<&str, ArenaError> {
+        let length = parts
+            .iter()
+            .try_fold(0usize, |acc, part| acc.checked_add(part.len()))
+            .ok_or(ArenaError::InsufficientMemory)?;
 
-type BlockPtr = NonNull<u8>;
-type BlockSize = usize;
-type BlockCursor = Cell<*mut u8>;
+        let layout = Layout::array::<u8>(length)?;
+        let ptr = self.try_alloc(layout)?;
+        unsafe {
+            let mut cursor = ptr;
+            for part in parts {
+                std::ptr::copy_nonoverlapping(part.as_ptr(), cursor, part.len());
+                cursor = cursor.add(part.len());
+            }
 
-const DEFAULT_BLOCK_SIZE: BlockSize = 64 * 1024;
+            // concatenation of valid UTF-8 fragments is valid UTF-8
+            let bytes = std::slice::from_raw_parts(ptr, length);
+            Ok(std::str::from_utf8_unchecked(bytes))
+        }
+    }
 
-#[repr(C)]
-struct Block {
-    start_ptr: BlockPtr,
-    end_ptr: BlockPtr,
-    curr_ptr: BlockCursor,
-    size: BlockSize,
-}
+    /// Zeroed byte buffer, skipping the generic `Layout::array` path.
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_bytes(&mut self, length: usize) -> Result<&mut [u8], ArenaError> {
+        if length == 0 {
+            return Ok(&mut []);
+        }
 
-impl Block {
-    pub fn new(size: BlockSize) -> Result<Self, ArenaError> {
-        if size == 0 {
-            return Err(ArenaError::ZeroSize);
+        let ptr = self.try_alloc(Layout::from_size_align(length, 1)?)?;
+        unsafe {
+            std::ptr::write_bytes(ptr, 0, length);
+            Ok(std::slice::from_raw_parts_mut(ptr, length))
         }
+    }
 
-        let alignment = align_of::<usize>();
-        let layout = Layout::from_size_align(size, alignment)?;
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn copy_bytes(&mut self, src: &[u8]) -> Result<&mut [u8], ArenaError> {
+        if src.is_empty() {
+            return Ok(&mut []);
+        }
 
+        let ptr = self.try_alloc(Layout::from_size_align(src.len(), 1)?)?;
         unsafe {
-            let ptr = alloc(layout);
-            if ptr.is_null() {
-                Err(ArenaError::InsufficientMemory)
-            } else {
-                let start_ptr = NonNull::new_unchecked(ptr);
+            std::ptr::copy_nonoverlapping(src.as_ptr(), ptr, src.len());
+            Ok(std::slice::from_raw_parts_mut(ptr, src.len()))
+        }
+    }
 
-                Ok(Self {
-                    start_ptr,
-                    end_ptr: start_ptr.add(size),
-                    curr_ptr: BlockCursor::new(ptr),
-                    size,
-                })
-            }
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn copy_slice<T: Copy>(&mut self, slice: &[T]) -> Result<&mut [T], ArenaError> {
+        assert_align::<T>();
+        let layout = Layout::array::<T>(slice.len())?;
+        let ptr = self.try_alloc(layout)? as *mut T;
+        unsafe {
+            std::ptr::copy_nonoverlapping(slice.as_ptr(), ptr, slice.len());
+            Ok(&mut *std::ptr::slice_from_raw_parts_mut(ptr, slice.len()))
         }
     }
 
-    pub fn alloc(&self, layout: Layout) -> Result<*mut u8, ArenaError> {
-        let size = layout.size();
-        let alignment = layout.align();
+    /// Concatenates the chunks of `iter` into one slice. The buffer grows
+    /// in place while it's the latest allocation and moves otherwise, use
+    /// `copy_from_iter_of_slices_exact` when the iterator can be cloned.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn copy_from_iter_of_slices<'s, T, I>(&mut self, iter: I) -> Result<&mut [T], ArenaError>
+    where
+        T: Copy + 's,
+        I: IntoIterator<Item = &'s [T]>,
+    {
+        let mut ptr = NonNull::<T>::dangling().as_ptr();
+        let mut length = 0usize;
+        let mut capacity = 0usize;
 
-        let old_ptr = self.curr_ptr.get();
+        for chunk in iter {
+            let needed = length
+                .checked_add(chunk.len())
+                .ok_or(ArenaError::InsufficientMemory)?;
 
-        let align_mask = !(alignment - 1);
-        let aligned = ((old_ptr as usize + alignment - 1) & align_mask) as *mut u8;
+            if needed > capacity {
+                let new_capacity = needed.max(capacity.saturating_mul(2));
+                let new_layout = Layout::array::<T>(new_capacity)?;
+                let grown = capacity > 0
+                    && self.grow_last(ptr as *mut u8, capacity * size_of::<T>(), new_layout.size());
 
-        let new_ptr = unsafe { aligned.add(size) };
-        if new_ptr > self.end_ptr.as_ptr() {
-            return Err(ArenaError::InsufficientMemory);
+                if !grown {
+                    let new_ptr = self.try_alloc(new_layout)? as *mut T;
+                    unsafe { std::ptr::copy_nonoverlapping(ptr, new_ptr, length) };
+                    ptr = new_ptr;
+                }
+                capacity = new_capacity;
+            }
+
+            unsafe { std::ptr::copy_nonoverlapping(chunk.as_ptr(), ptr.add(length), chunk.len()) };
+            length = needed;
         }
 
-        self.curr_ptr.set(new_ptr);
-        Ok(aligned)
+        if capacity == 0 {
+            return Ok(&mut []);
+        }
+
+        let slice = unsafe { &mut *std::ptr::slice_from_raw_parts_mut(ptr, capacity) };
+        Ok(unsafe { self.shrink_last(slice, length) })
     }
 
-    #[inline]
-    pub fn rewind_to(&mut self, save_point: *mut u8) {
-        self.curr_ptr.set(save_point);
+    /// Like `copy_from_iter_of_slices`, but walks a clone of `iter` first
+    /// to allocate the exact total once. `ArenaError::LengthMismatch` when
+    /// the two walks disagree on the total.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn copy_from_iter_of_slices_exact<'s, T, I>(
+        &mut self,
+        iter: I,
+    ) -> Result<&mut [T], ArenaError>
+    where
+        T: Copy + 's,
+        I: IntoIterator<Item = &'s [T]>,
+        I::IntoIter: Clone,
+    {
+        let iter = iter.into_iter();
+        let length = iter
+            .clone()
+            .try_fold(0usize, |acc, chunk| acc.checked_add(chunk.len()))
+            .ok_or(ArenaError::InsufficientMemory)?;
+
+        assert_align::<T>();
+        let layout = Layout::array::<T>(length)?;
+        let ptr = self.try_alloc(layout)? as *mut T;
+        // a `Clone` isn't trusted to repeat itself, the copy stays within
+        // the `length` allocated
+        let mut written = 0;
+        for chunk in iter {
+            let count = chunk.len().min(length - written);
+            unsafe { std::ptr::copy_nonoverlapping(chunk.as_ptr(), ptr.add(written), count) };
+            written += count;
+            if count < chunk.len() {
+                return Err(ArenaError::LengthMismatch);
+            }
+        }
+        if written < length {
+            return Err(ArenaError::LengthMismatch);
+        }
+
+        Ok(unsafe { &mut *std::ptr::slice_from_raw_parts_mut(ptr, length) })
     }
 
     #[inline]
     pub fn reset(&mut self) {
-        self.curr_ptr.set(self.start_ptr.as_ptr());
+        #[cfg(feature = "timing")]
+        let started = std::time::Instant::now();
+
+        self.run_drops();
+        self.free_spills(0);
+        #[cfg(feature = "stats")]
+        self.allocations.clear();
+        #[cfg(feature = "stats")]
+        self.phases.clear();
+        for block in &mut self.blocks {
+            block.get_mut().reset();
+        }
+        self.active = 0;
+
+        if self.config.promote_largest {
+            self.promote_largest_block();
+        }
+
+        #[cfg(feature = "timing")]
+        self.report_reset(started);
     }
 
     #[inline]
     pub fn reset_zeroed(&mut self) {
-        self.reset();
-        unsafe { std::ptr::write_bytes(self.start_ptr.as_ptr(), 0, self.size) };
+        #[cfg(feature = "timing")]
+        let started = std::time::Instant::now();
+
+        self.run_drops();
+        self.free_spills(0);
+        #[cfg(feature = "stats")]
+        self.allocations.clear();
+        #[cfg(feature = "stats")]
+        self.phases.clear();
+        for block in &mut self.blocks {
+            block.get_mut().reset_zeroed();
+        }
+        self.active = 0;
+
+        #[cfg(feature = "timing")]
+        self.report_reset(started);
     }
 
-    #[inline]
-    pub fn remaining(&self) -> BlockSize {
-        (self.end_ptr.as_ptr() as usize) - (self.curr_ptr.as_ptr() as usize)
+    /// Installs a hook called with how long every `reset`/`reset_zeroed`
+    /// took, replacing the previous one.
+    #[cfg(feature = "timing")]
+    pub fn on_reset(&mut self, hook: impl FnMut(std::time::Duration) + 'static) {
+        self.reset_hook = Some(Box::new(hook));
     }
 
-    #[cfg(test)]
-    pub fn as_ptr(&self) -> *mut u8 {
-        self.start_ptr.as_ptr()
+    #[cfg(feature = "timing")]
+    #[inline]
+    fn report_reset(&mut self, started: std::time::Instant) {
+        if let Some(hook) = &mut self.reset_hook {
+            hook(started.elapsed());
+        }
     }
 
-    #[cfg(feature = "debug")]
+    /// Moves the biggest block to the front so first-fit prefers it, the
+    /// other blocks keep their relative order.
+    fn promote_largest_block(&mut self) {
+        let largest = self
+            .blocks
+            .iter_mut()
+            .map(|block| block.get_mut().size)
+            .enumerate()
+            .max_by_key(|&(i, size)| (size, std::cmp::Reverse(i)))
+            .map(|(i, _)| i);
+
+        if let Some(largest) = largest {
+            self.blocks[..=largest].rotate_right(1);
+        }
+    }
+
+    /// # Safety
+    /// `ptr` must point to `len` initialized `T`s living in this arena.
+    #[inline]
+    unsafe fn register_drop<T>(&mut self, ptr: *mut T, len: usize) {
+        if std::mem::needs_drop::<T>() && len > 0 {
+            self.drops.push(DropThunk {
+                ptr: ptr as *mut u8,
+                len,
+                drop: drop_thunk::<T>,
+                seq: self.drop_seq,
+            });
+            self.drop_seq += 1;
+        }
+    }
+
+    /// Cancels every pending destructor without running it. Meant for values
+    /// whose ownership was moved out of the arena; anything else still
+    /// tracked is leaked.
+    pub fn forget_drops(&mut self) {
+        self.drops.clear();
+    }
+
+    /// Like `forget_drops`, limited to destructors registered after
+    /// `snapshot` was taken.
+    pub fn forget_drops_since(&mut self, snapshot: &ArenaSnapshot) {
+        let from = self
+            .drops
+            .partition_point(|thunk| thunk.seq < snapshot.drop_seq);
+        self.drops.truncate(from);
+    }
+
+    /// Runs every pending destructor.
+    fn run_drops(&mut self) {
+        let pending = std::mem::take(&mut self.drops);
+        run_in_order(&pending, self.config.drop_order);
+    }
+
+    /// Runs and removes the destructors numbered `from_seq` or higher whose
+    /// value `reclaimed` says is handed back. Older ones are never affected
+    /// by a rewind: their values sat below the cursors the snapshot saved.
+    fn run_reclaimed_drops(
+        drops: &mut Vec<DropThunk>,
+        order: DropOrder,
+        from_seq: u64,
+        reclaimed: impl Fn(*mut u8) -> bool,
+    ) {
+        let from = drops.partition_point(|thunk| thunk.seq < from_seq);
+        if from == drops.len() {
+            return;
+        }
+
+        let (doomed, kept): (Vec<_>, Vec<_>) = drops
+            .split_off(from)
+            .into_iter()
+            .partition(|thunk| reclaimed(thunk.ptr));
+        drops.extend(kept);
+        run_in_order(&doomed, order);
+    }
+
+    /// Frees every block past the first `len` (at least one block is kept).
+    /// Destructors of values in those blocks run first.
+    pub fn truncate_blocks(&mut self, len: usize) -> ShrinkResult {
+        let len = len.max(1);
+        self.release_blocks(|idx, _| idx >= len)
+    }
+
+    /// Frees every empty block except the first one.
+    pub fn shrink_to_fit(&mut self) -> ShrinkResult {
+        self.release_blocks(|idx, block| idx > 0 && block.used() == 0)
+    }
+
+    fn release_blocks(&mut self, mut release: impl FnMut(usize, &Block) -> bool) -> ShrinkResult {
+        let active_id = self.current_block().id;
+        let (released, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.blocks)
+            .into_iter()
+            .enumerate()
+            .partition(|(idx, block)| release(*idx, unsafe { &*block.get() }));
+
+        self.blocks = kept.into_iter().map(|(_, block)| block).collect();
+        if released.is_empty() {
+            return ShrinkResult::default();
+        }
+
+        let contains = |ptr: *mut u8| {
+            released
+                .iter()
+                .any(|(_, block)| unsafe { &*block.get() }.contains(ptr))
+        };
+        Self::run_reclaimed_drops(&mut self.drops, self.config.drop_order, 0, contains);
+
+        #[cfg(feature = "stats")]
+        self.allocations.retain(|record| !contains(record.ptr));
+
+        self.active = self.block_index(active_id).unwrap_or(0);
+        ShrinkResult {
+            freed_bytes: released
+                .iter()
+                .map(|(_, block)| unsafe { &*block.get() }.size)
+                .sum(),
+            freed_blocks: released.len(),
+        }
+    }
+
+    /// Frees spilled allocations from index `from` on, newest first.
+    fn free_spills(&mut self, from: usize) {
+        if from >= self.spills.len() {
+            return;
+        }
+
+        for (ptr, layout) in self.spills.drain(from..).rev() {
+            unsafe { dealloc(ptr.as_ptr(), layout) };
+        }
+    }
+
+    /// Heap-allocates `layout` on its own, outside of every block.
+    fn spill(&mut self, layout: Layout) -> Result<*mut u8, ArenaError> {
+        // a zero-sized request still gets a unique, freeable address
+        let layout = Layout::from_size_align(layout.size().max(1), layout.align())?;
+        let ptr = NonNull::new(unsafe { alloc(layout) }).ok_or(ArenaError::InsufficientMemory)?;
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(size = layout.size(), "arena spill");
+
+        self.spills.push((ptr, layout));
+        self.system_allocs += 1;
+        Ok(ptr.as_ptr())
+    }
+
+    /// Makes sure at least `additional` contiguous bytes are available,
+    /// growing the arena if needed.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let available = self.largest_available();
+        if available >= additional {
+            return Ok(());
+        }
+
+        if self.config.on_overflow != OnOverflow::Grow {
+            return Err(TryReserveError {
+                available,
+                growth_attempted: false,
+            });
+        }
+
+        match self.alloc_new_block(additional, BLOCK_ALIGN) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(TryReserveError {
+                available,
+                growth_attempted: true,
+            }),
+        }
+    }
+
+    /// Makes sure one contiguous run of `bytes` starting at an
+    /// `align`-aligned address is available, adding a block if none fits.
+    pub fn reserve_aligned(&mut self, bytes: usize, align: usize) -> Result<(), ArenaError> {
+        let layout = Layout::from_size_align(bytes, align)?;
+        let fits = self
+            .blocks
+            .iter()
+            .any(|block| unsafe { &*block.get() }.fits(layout));
+        if fits {
+            return Ok(());
+        }
+
+        // regular blocks start `BLOCK_ALIGN`-aligned, only stricter
+        // alignments need room to pad
+        let padded = if align > BLOCK_ALIGN {
+            bytes.checked_add(align - 1)
+        } else {
+            Some(bytes)
+        };
+        self.alloc_new_block(padded.ok_or(ArenaError::InsufficientMemory)?, align)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn largest_available(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| unsafe { &*block.get() }.remaining())
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    fn try_alloc(&mut self, layout: Layout) -> Result<*mut u8, ArenaError> {
+        let ptr = match self.try_get_block(layout) {
+            Some(idx) => {
+                self.active = idx;
+                self.blocks[idx].get_mut().alloc(layout)?
+            }
+            None if self.config.on_overflow == OnOverflow::Spill => self.spill(layout)?,
+            None => self.alloc_in_new_block(layout)?,
+        };
+
+        #[cfg(feature = "tracing-verbose")]
+        tracing::trace!(
+            size = layout.size(),
+            align = layout.align(),
+            "arena allocation"
+        );
+
+        #[cfg(feature = "stats")]
+        self.allocations.push(AllocRecord {
+            ptr,
+            size: layout.size(),
+            #[cfg(feature = "debug")]
+            location: std::panic::Location::caller(),
+        });
+
+        Ok(ptr)
+    }
+
+    /// Bumps the active block by `size` bytes aligned to `mask + 1`, the
+    /// fast path of `TypedAllocator`. `None` when the block lacks room.
+    #[cfg(not(any(feature = "stats", feature = "tracing-verbose")))]
+    #[inline]
+    pub(crate) fn bump_active(&mut self, size: usize, mask: usize) -> Option<*mut u8> {
+        let block = self.blocks[self.active].get_mut();
+        let curr = block.curr_ptr.get();
+        let start = (curr as usize).checked_add(mask)? & !mask;
+        let end = start.checked_add(size)?;
+        if end > block.end_ptr.as_ptr() as usize {
+            return None;
+        }
+
+        let ptr = curr.wrapping_add(start - curr as usize);
+        block.curr_ptr.set(ptr.wrapping_add(size));
+        Some(ptr)
+    }
+
+    /// `try_alloc` for shared access: only existing blocks are used and the
+    /// allocation isn't recorded by `stats`.
+    #[inline]
+    fn try_alloc_shared(&self, layout: Layout) -> Result<*mut u8, ArenaError> {
+        let idx = self
+            .try_get_block(layout)
+            .ok_or(ArenaError::InsufficientMemory)?;
+
+        // the cursor is a `Cell`, and every `&mut Block` access needs
+        // `&mut self`, which can't coexist with this borrow
+        unsafe { &*self.blocks[idx].get() }.alloc(layout)
+    }
+
+    /// Serves `layout` from a fresh block aligned to at least
+    /// `layout.align()`, so the allocation starts the block unpadded.
+    fn alloc_in_new_block(&mut self, layout: Layout) -> Result<*mut u8, ArenaError> {
+        self.alloc_new_block(layout.size(), layout.align())?
+            .alloc(layout)
+    }
+
+    /// Number of live allocations per power-of-two size class.
+    #[cfg(feature = "stats")]
+    pub fn size_class_histogram(&self) -> std::collections::BTreeMap<usize, usize> {
+        let mut histogram = std::collections::BTreeMap::new();
+        for record in &self.allocations {
+            *histogram
+                .entry(record.size.next_power_of_two())
+                .or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Starts a new accounting phase named `name`, ending the previous one.
+    /// Phases are cleared on reset.
+    #[cfg(feature = "stats")]
+    pub fn phase(&mut self, name: &str) {
+        self.phases.push((name.to_owned(), self.used()));
+    }
+
+    /// Bytes used during each phase, the last one running up to now.
+    /// Allocations before the first marker aren't attributed to any phase.
+    #[cfg(feature = "stats")]
+    pub fn phase_usage(&self) -> Vec<(String, usize)> {
+        let ends = self.phases.iter().skip(1).map(|&(_, used)| used);
+        self.phases
+            .iter()
+            .zip(ends.chain([self.used()]))
+            .map(|((name, start), end)| (name.clone(), end.saturating_sub(*start)))
+            .collect()
+    }
+
+    /// Bytes of every live allocation, in allocation order.
+    ///
+    /// # Safety
+    /// Every byte of every live allocation must be initialized: no
+    /// `alloc_uninit` left unwritten and no values with padding.
+    #[cfg(feature = "stats")]
+    pub unsafe fn regions(&self) -> impl Iterator<Item = &[u8]> {
+        self.allocations
+            .iter()
+            .map(|record| unsafe { std::slice::from_raw_parts(record.ptr, record.size) })
+    }
+
+    /// Shortens `slice` to `new_len` elements. When `slice` is the most
+    /// recent allocation of its block the tail is handed back to the arena,
+    /// otherwise the shrink is only logical.
+    ///
+    /// # Safety
+    /// `slice` must be a single allocation from this arena.
+    pub unsafe fn shrink_last<'a, T>(&mut self, slice: &'a mut [T], new_len: usize) -> &'a mut [T] {
+        if new_len >= slice.len() {
+            return slice;
+        }
+
+        let ptr = slice.as_mut_ptr() as *mut u8;
+        let old_end = ptr.wrapping_add(size_of_val(slice));
+        let new_end = ptr.wrapping_add(new_len * size_of::<T>());
+
+        // a tracked slice drops its tail now so its thunk never covers
+        // memory handed back to the arena
+        if let Some(i) = self
+            .drops
+            .iter()
+            .rposition(|thunk| thunk.ptr == ptr && thunk.len == slice.len())
+        {
+            unsafe { std::ptr::drop_in_place(&mut slice[new_len..]) };
+            match new_len {
+                0 => _ = self.drops.remove(i),
+                _ => self.drops[i].len = new_len,
+            }
+        }
+
+        if let Some(block) = self
+            .blocks
+            .iter_mut()
+            .map(UnsafeCell::get_mut)
+            .find(|block| block.curr_ptr.get() == old_end && block.start_ptr.as_ptr() <= ptr)
+        {
+            block.curr_ptr.set(new_end);
+            #[cfg(feature = "stats")]
+            self.resize_record(ptr, new_len * size_of::<T>());
+        }
+
+        &mut slice[..new_len]
+    }
+
+    /// Extends the allocation ending at `ptr + old_size` in place when it's
+    /// the most recent one in its block and there's room left.
+    pub(crate) fn grow_last(&mut self, ptr: *mut u8, old_size: usize, new_size: usize) -> bool {
+        let old_end = ptr.wrapping_add(old_size);
+        for block in &mut self.blocks {
+            let block = block.get_mut();
+            if block.curr_ptr.get() != old_end || block.start_ptr.as_ptr() > ptr {
+                continue;
+            }
+
+            let new_end = (ptr as usize).checked_add(new_size);
+            if new_end.is_none_or(|end| end > block.end_ptr.as_ptr() as usize) {
+                return false;
+            }
+
+            block.curr_ptr.set(ptr.wrapping_add(new_size));
+            #[cfg(feature = "stats")]
+            self.resize_record(ptr, new_size);
+            return true;
+        }
+        false
+    }
+
+    #[cfg(feature = "stats")]
+    fn resize_record(&mut self, ptr: *mut u8, size: usize) {
+        if let Some(record) = self.allocations.iter_mut().rev().find(|r| r.ptr == ptr) {
+            record.size = size;
+        }
+    }
+
+    #[inline]
+    fn alloc_new_block(&mut self, size: BlockSize, align: usize) -> Result<&mut Block, ArenaError> {
+        if self.config.on_overflow != OnOverflow::Grow {
+            return Err(ArenaError::CapacityExceeded);
+        }
+
+        let align = align.max(BLOCK_ALIGN);
+        let mut block = Block::with_config(self.config.block_size.max(size), align, &self.config)?;
+        block.id = self.next_block_id;
+        self.next_block_id += 1;
+        self.system_allocs += 1;
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            block_id = block.id,
+            size = block.size,
+            requested = size,
+            reason = if size > self.config.block_size {
+                "oversized"
+            } else {
+                "exhausted"
+            },
+            "arena new block"
+        );
+
+        self.blocks.push(UnsafeCell::new(block));
+        self.active = self.blocks.len() - 1;
+        Ok(self.blocks.last_mut().unwrap().get_mut())
+    }
+
+    /// Index of the block `layout` should go to, according to the
+    /// configured `BlockFit`.
+    #[inline]
+    fn try_get_block(&self, layout: Layout) -> Option<usize> {
+        // the padding up to `layout.align()` counts, so a block is only
+        // picked when `Block::alloc` will succeed in it
+        let mut candidates = self
+            .blocks
+            .iter()
+            .map(|block| unsafe { &*block.get() })
+            .map(|block| (block.fits(layout), block.remaining()))
+            .enumerate()
+            .filter(|&(_, (fits, _))| fits)
+            .map(|(idx, (_, remaining))| (idx, remaining));
+
+        match self.config.fit {
+            BlockFit::FirstFit => candidates.next(),
+            BlockFit::BestFit => candidates.min_by_key(|&(_, remaining)| remaining),
+        }
+        .map(|(idx, _)| idx)
+    }
+
+    #[inline]
+    fn block_index(&self, id: BlockId) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| unsafe { &*block.get() }.id == id)
+    }
+
+    /// Bytes allocated since `snapshot` was taken: the growth of the
+    /// snapshot's block and the blocks after it, plus everything in the
+    /// blocks created since.
+    pub fn bytes_since(&self, snapshot: &ArenaSnapshot) -> usize {
+        let Some(block_idx) = self.block_index(snapshot.block_id) else {
+            return 0;
+        };
+
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, block)| {
+                let block = unsafe { &*block.get() };
+                let saved = snapshot.cursor_for(block_idx, idx, block)?;
+                Some((block.curr_ptr.get() as usize).saturating_sub(saved as usize))
+            })
+            .sum()
+    }
+
+    /// Calls `f` with the used bytes `[start, cursor)` of every block.
+    ///
+    /// # Safety
+    /// Every used byte must have been initialized, padding included, e.g. by
+    /// only storing byte slices. Whatever `f` writes must leave every live
+    /// value valid, in particular the ones with registered destructors.
+    pub unsafe fn for_each_block_mut(&mut self, mut f: impl FnMut(&mut [u8])) {
+        for block in &mut self.blocks {
+            let block = block.get_mut();
+            let used =
+                unsafe { std::slice::from_raw_parts_mut(block.start_ptr.as_ptr(), block.used()) };
+            f(used);
+        }
+    }
+
+    /// Bytes consumed across all blocks, alignment padding included.
+    pub fn used(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| unsafe { &*block.get() }.used())
+            .sum()
+    }
+
+    /// One `IoSlice` per non-empty block covering its used bytes, in block
+    /// order, for a single `write_vectored` of the whole arena.
+    ///
+    /// # Safety
+    /// Every used byte must be initialized, including alignment padding
+    /// between allocations and padding inside values.
+    pub unsafe fn as_io_slices(&self) -> Vec<std::io::IoSlice<'_>> {
+        self.blocks
+            .iter()
+            .map(|block| unsafe { &*block.get() }.used_bytes())
+            .filter(|bytes| !bytes.is_empty())
+            .map(std::io::IoSlice::new)
+            .collect()
+    }
+
+    /// Free bytes left across all blocks, not necessarily contiguous.
+    pub fn remaining(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| unsafe { &*block.get() }.remaining())
+            .sum()
+    }
+
+    /// Total size of all blocks, always `used() + remaining()`.
+    pub fn capacity(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| unsafe { &*block.get() }.size)
+            .sum()
+    }
+
+    /// How many times the arena asked the system for memory (blocks and
+    /// spills). Should level off once a reset-and-reuse cycle is warm, an
+    /// arena from `from_parts` starts at zero.
+    #[inline]
+    pub fn system_alloc_count(&self) -> u64 {
+        self.system_allocs
+    }
+
+    #[inline]
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Same as `capacity`, named for metrics exporters.
+    #[inline]
+    pub fn reserved_bytes(&self) -> usize {
+        self.capacity()
+    }
+
+    /// Same as `used`, named for metrics exporters.
+    #[inline]
+    pub fn allocated_bytes(&self) -> usize {
+        self.used()
+    }
+
+    /// Free bytes in the active block: how much fits before the arena
+    /// looks at other blocks or grows.
+    #[inline]
+    pub fn remaining_bytes(&self) -> usize {
+        self.current_block().remaining()
+    }
+
+    /// Overall `used / capacity` ratio, 0.0 for an empty arena.
+    pub fn utilization(&self) -> f64 {
+        ratio(self.used(), self.capacity())
+    }
+
+    /// `used / size` ratio of every block, in block order.
+    pub fn block_utilization(&self) -> Vec<f64> {
+        self.blocks
+            .iter()
+            .map(|block| {
+                let block = unsafe { &*block.get() };
+                ratio(block.used(), block.size)
+            })
+            .collect()
+    }
+
+    /// Size new regular blocks get after the rounding policy is applied.
+    #[inline]
+    pub fn effective_block_size(&self) -> usize {
+        self.config.effective_block_size()
+    }
+
+    /// Block the cursor-level operations (`snapshot`, `align_to`) act on,
+    /// the one that served the latest allocation.
+    #[inline]
+    fn current_block(&self) -> &Block {
+        unsafe { &*self.blocks[self.active].get() }
+    }
+
+    /// Hints the CPU to pull the next `bytes` of the active block into cache
+    /// ahead of a large allocation. Only covers what fits in the block and
+    /// does nothing on targets without a prefetch instruction.
+    #[inline]
+    pub fn prefetch(&self, bytes: usize) {
+        let block = self.current_block();
+        let start = block.curr_ptr.get();
+        let len = bytes.min(block.remaining());
+
+        #[cfg(target_arch = "x86_64")]
+        for offset in (0..len).step_by(BLOCK_ALIGN) {
+            use std::arch::x86_64::{_MM_HINT_T0, _mm_prefetch};
+            // a prefetch never faults and sse is part of the x86_64 baseline
+            unsafe { _mm_prefetch::<_MM_HINT_T0>(start.wrapping_add(offset) as *const i8) };
+        }
+
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = (start, len);
+    }
+
+    /// Pads the current block's cursor up to `align`, returning how many
+    /// bytes of padding were consumed.
+    pub fn align_to(&mut self, align: usize) -> Result<usize, ArenaError> {
+        if !align.is_power_of_two() {
+            return Err(ArenaError::BadAlignment);
+        }
+
+        let block = self.current_block();
+        let curr = block.curr_ptr.get() as usize;
+        let padding = curr.next_multiple_of(align) - curr;
+        if padding > block.remaining() {
+            return Err(ArenaError::InsufficientMemory);
+        }
+
+        block
+            .curr_ptr
+            .set(block.curr_ptr.get().wrapping_add(padding));
+        Ok(padding)
+    }
+
+    pub fn snapshot(&self) -> ArenaSnapshot {
+        let block = self.current_block();
+        let offset = block.curr_ptr.get();
+        let later = self.blocks[self.active + 1..]
+            .iter()
+            .map(|block| unsafe { &*block.get() })
+            .filter(|block| block.used() > 0)
+            .map(|block| (block.id, block.curr_ptr.get()))
+            .collect();
+
+        ArenaSnapshot {
+            arena_uid: self.uid,
+            block_id: block.id,
+            offset,
+            later,
+            next_block_id: self.next_block_id,
+            drop_seq: self.drop_seq,
+            spills: self.spills.len(),
+            #[cfg(feature = "stats")]
+            allocations: self.allocations.len(),
+        }
+    }
+
+    /// Rolls back to `snapshot`. A snapshot whose block was released by
+    /// `truncate_blocks` or `shrink_to_fit` panics in debug builds and is
+    /// ignored otherwise, see `try_rewind_to`.
+    #[inline]
+    pub fn rewind_to(&mut self, snapshot: ArenaSnapshot) {
+        let result = self.try_rewind_to(snapshot);
+        debug_assert!(result.is_ok(), "snapshot taken in a released block");
+    }
+
+    /// Like `rewind_to`, but reports `ArenaError::StaleSnapshot` without
+    /// touching the arena when the snapshot's block no longer exists or the
+    /// snapshot was taken from another arena.
+    pub fn try_rewind_to(&mut self, snapshot: ArenaSnapshot) -> Result<(), ArenaError> {
+        let Some(block_idx) = self.block_index(snapshot.block_id) else {
+            return Err(ArenaError::StaleSnapshot);
+        };
+
+        self.rewind_blocks(
+            snapshot.arena_uid,
+            snapshot.drop_seq,
+            snapshot.spills,
+            #[cfg(feature = "stats")]
+            snapshot.allocations,
+            |idx, block| snapshot.cursor_for(block_idx, idx, block),
+        )?;
+        self.active = block_idx;
+        Ok(())
+    }
+
+    /// Moves every block's cursor back to the one `target` gives, if any.
+    /// Destructors of values in the memory handed back run, spills from
+    /// index `spills` on are freed. Nothing changes unless the snapshot is
+    /// from this arena and every target lies in its block.
+    fn rewind_blocks(
+        &mut self,
+        arena_uid: u64,
+        drop_seq: u64,
+        spills: usize,
+        #[cfg(feature = "stats")] allocations: usize,
+        target: impl Fn(usize, &Block) -> Option<*mut u8>,
+    ) -> Result<(), ArenaError> {
+        let in_bounds = |block: &Block, cursor: *mut u8| {
+            block.start_ptr.as_ptr() <= cursor && cursor <= block.end_ptr.as_ptr()
+        };
+        let valid = self.blocks.iter().enumerate().all(|(idx, block)| {
+            let block = unsafe { &*block.get() };
+            target(idx, block).is_none_or(|cursor| in_bounds(block, cursor))
+        });
+        if arena_uid != self.uid || !valid {
+            return Err(ArenaError::StaleSnapshot);
+        }
+
+        let spills = spills.min(self.spills.len());
+        let blocks = &self.blocks;
+        let freed = &self.spills[spills..];
+        let reclaimed = |ptr: *mut u8| {
+            let in_block = blocks.iter().enumerate().any(|(idx, block)| {
+                let block = unsafe { &*block.get() };
+                target(idx, block).is_some_and(|from| from <= ptr && block.contains(ptr))
+            });
+            in_block
+                || freed.iter().any(|(start, layout)| {
+                    let start = start.as_ptr();
+                    start <= ptr && ptr < start.wrapping_add(layout.size())
+                })
+        };
+
+        Self::run_reclaimed_drops(&mut self.drops, self.config.drop_order, drop_seq, reclaimed);
+        #[cfg(feature = "stats")]
+        {
+            let since = self
+                .allocations
+                .split_off(allocations.min(self.allocations.len()));
+            self.allocations
+                .extend(since.into_iter().filter(|record| !reclaimed(record.ptr)));
+        }
+        self.free_spills(spills);
+
+        for (idx, block) in self.blocks.iter_mut().enumerate() {
+            let block = block.get_mut();
+            if let Some(cursor) = target(idx, block) {
+                block.rewind_to(cursor);
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the cursor of every block, the heavier counterpart of
+    /// `snapshot` for allocations that land in blocks before the active one.
+    pub fn snapshot_all(&self) -> FullSnapshot {
+        let cursors = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let block = unsafe { &*block.get() };
+                (block.id, block.curr_ptr.get())
+            })
+            .collect();
+
+        FullSnapshot {
+            arena_uid: self.uid,
+            cursors,
+            drop_seq: self.drop_seq,
+            spills: self.spills.len(),
+            #[cfg(feature = "stats")]
+            allocations: self.allocations.len(),
+        }
+    }
+
+    /// Restores every block's cursor from `snapshot`, blocks created since
+    /// are reset. Like `try_rewind_to`, a snapshot from another arena or
+    /// with a cursor outside its block is rejected without changes.
+    pub fn rewind_all(&mut self, snapshot: FullSnapshot) -> Result<(), ArenaError> {
+        self.rewind_blocks(
+            snapshot.arena_uid,
+            snapshot.drop_seq,
+            snapshot.spills,
+            #[cfg(feature = "stats")]
+            snapshot.allocations,
+            |_, block| {
+                let saved = snapshot.cursors.iter().find(|(id, _)| *id == block.id);
+                Some(saved.map_or(block.start_ptr.as_ptr(), |&(_, cursor)| cursor))
+            },
+        )
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn dump(&self) {
+        println!("Arena Debug Dump");
+        println!("================");
+        println!("Total blocks: {}", self.blocks.len());
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            unsafe { &*block.get() }.dump(i);
+        }
+
+        println!("Live allocations: {}", self.allocations.len());
+        for record in &self.allocations {
+            println!(
+                "  0x{:x}: {:>6} bytes at {}",
+                record.ptr as usize, record.size, record.location
+            );
+        }
+
+        println!();
+    }
+}
+
+fn next_arena_uid() -> u64 {
+    static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+#[inline]
+fn ratio(used: usize, capacity: usize) -> f64 {
+    if capacity == 0 {
+        0.0
+    } else {
+        used as f64 / capacity as f64
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        self.run_drops();
+        self.free_spills(0);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaConfig {
+    block_size: BlockSize,
+    on_overflow: OnOverflow,
+    drop_order: DropOrder,
+    promote_largest: bool,
+    rounding: BlockRounding,
+    fit: BlockFit,
+    zero_slices: bool,
+    #[cfg(all(feature = "guard-pages", unix))]
+    guard_pages: bool,
+}
+
+impl ArenaConfig {
+    pub fn new() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            on_overflow: OnOverflow::Grow,
+            drop_order: DropOrder::Lifo,
+            promote_largest: false,
+            rounding: BlockRounding::Exact,
+            fit: BlockFit::FirstFit,
+            zero_slices: true,
+            #[cfg(all(feature = "guard-pages", unix))]
+            guard_pages: false,
+        }
+    }
+
+    /// Size of every regular block, oversized allocations get their own
+    /// bigger block.
+    pub fn block_size(mut self, size: usize) -> Self {
+        self.block_size = size;
+        self
+    }
+
+    /// Shorthand for `on_overflow(OnOverflow::Error)` when set, or
+    /// `OnOverflow::Grow` when not.
+    pub fn fixed(mut self, fixed: bool) -> Self {
+        self.on_overflow = if fixed {
+            OnOverflow::Error
+        } else {
+            OnOverflow::Grow
+        };
+        self
+    }
+
+    /// What an allocation does when no block has room for it.
+    pub fn on_overflow(mut self, policy: OnOverflow) -> Self {
+        self.on_overflow = policy;
+        self
+    }
+
+    /// Order tracked destructors run in, `DropOrder::Lifo` by default.
+    ///
+    /// # Safety
+    /// Under `DropOrder::Fifo` no tracked destructor may read a value
+    /// registered before it, that value is already dropped.
+    pub unsafe fn drop_order(mut self, order: DropOrder) -> Self {
+        self.drop_order = order;
+        self
+    }
+
+    /// When set `reset` moves the largest block to the front, so the next
+    /// cycle fills it first instead of asking for new blocks.
+    pub fn promote_largest(mut self, promote: bool) -> Self {
+        self.promote_largest = promote;
+        self
+    }
+
+    /// How requested block sizes are rounded before allocating a block.
+    pub fn rounding(mut self, rounding: BlockRounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// How an allocation picks among the blocks that could hold it.
+    pub fn fit(mut self, fit: BlockFit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    /// Whether `alloc_slice` zeroes the memory it returns, on by default.
+    ///
+    /// # Safety
+    /// With zeroing off `alloc_slice` hands out uninitialized memory, the
+    /// caller must write every element before reading it.
+    pub unsafe fn zero_slices(mut self, zero: bool) -> Self {
+        self.zero_slices = zero;
+        self
+    }
+
+    /// Size regular blocks really get once rounding is applied.
+    pub fn effective_block_size(&self) -> usize {
+        let size = self
+            .rounding
+            .apply(self.block_size)
+            .unwrap_or(self.block_size);
+
+        #[cfg(all(feature = "guard-pages", unix))]
+        if self.guard_pages {
+            let page = guard::page_size();
+            return size.checked_next_multiple_of(page).unwrap_or(size);
+        }
+
+        size
+    }
+
+    /// Maps every block with `mmap` followed by a `PROT_NONE` page. Block
+    /// sizes are rounded up to whole pages so the guard sits right at the
+    /// block end.
+    #[cfg(all(feature = "guard-pages", unix))]
+    pub fn guard_pages(mut self, enabled: bool) -> Self {
+        self.guard_pages = enabled;
+        self
+    }
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rounding applied to the size of every new block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlockRounding {
+    /// use the requested size as is
+    #[default]
+    Exact,
+
+    /// round up to the next power of two
+    PowerOfTwo,
+
+    /// round up to a multiple of `PAGE_SIZE`
+    Page,
+}
+
+impl BlockRounding {
+    #[inline]
+    fn apply(self, size: usize) -> Option<usize> {
+        match self {
+            BlockRounding::Exact => Some(size),
+            BlockRounding::PowerOfTwo => size.checked_next_power_of_two(),
+            BlockRounding::Page => size.checked_next_multiple_of(PAGE_SIZE),
+        }
+    }
+}
+
+/// What happens when an allocation doesn't fit in any block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnOverflow {
+    /// add a new block
+    #[default]
+    Grow,
+
+    /// fail with `ArenaError::CapacityExceeded`, the arena keeps its first
+    /// block only
+    Error,
+
+    /// heap-allocate the object on its own; it isn't counted by `used` or
+    /// `capacity` and is freed on reset, rewind or drop
+    Spill,
+}
+
+/// Block selection strategy for allocations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlockFit {
+    /// first block with enough room
+    #[default]
+    FirstFit,
+
+    /// block with the least room that still fits, keeps big gaps for big
+    /// allocations
+    BestFit,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Atomic integer types accepted by `Arena::alloc_atomic`.
+pub trait Atomic: sealed::Sealed {}
+
+macro_rules! impl_atomic {
+    ($($(#[$attr:meta])* $ty:ident),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            impl sealed::Sealed for std::sync::atomic::$ty {}
+            $(#[$attr])*
+            impl Atomic for std::sync::atomic::$ty {}
+        )*
+    };
+}
+
+impl_atomic!(
+    AtomicBool,
+    AtomicI8,
+    AtomicU8,
+    AtomicI16,
+    AtomicU16,
+    AtomicI32,
+    AtomicU32,
+    #[cfg(target_has_atomic = "64")]
+    AtomicI64,
+    #[cfg(target_has_atomic = "64")]
+    AtomicU64,
+    AtomicIsize,
+    AtomicUsize,
+);
+
+/// Types for which the all-zero bit pattern is a valid value, accepted by
+/// `Arena::alloc_zeroed` and `Arena::alloc_slice_zeroed`.
+///
+/// # Safety
+/// Zeroed memory must be a valid `T`. Prefer `derive_zeroable!` (feature
+/// `zeroable-derive`) over implementing this by hand.
+pub unsafe trait Zeroable: Sized {}
+
+macro_rules! impl_zeroable {
+    ($($ty:ty),* $(,)?) => {
+        $(unsafe impl Zeroable for $ty {})*
+    };
+}
+
+impl_zeroable!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+);
+
+unsafe impl<T: Zeroable, const N: usize> Zeroable for [T; N] {}
+unsafe impl<T> Zeroable for MaybeUninit<T> {}
+unsafe impl<T: Zeroable> Zeroable for Cell<T> {}
+unsafe impl<T: Zeroable> Zeroable for std::num::Wrapping<T> {}
+unsafe impl<T> Zeroable for *const T {}
+unsafe impl<T> Zeroable for *mut T {}
+unsafe impl<T> Zeroable for Option<NonNull<T>> {}
+unsafe impl<T: Atomic> Zeroable for T {}
+
+#[doc(hidden)]
+#[cfg(feature = "zeroable-derive")]
+pub fn __assert_zeroable<T: Zeroable>(_: &T) {}
+
+/// Implements `Zeroable` for a struct after checking, at compile time, that
+/// every field is `Zeroable`. All fields must be listed; the check
+/// destructures the struct so a missing one is an error.
+///
+/// ```
+/// use arena::{Arena, derive_zeroable};
+///
+/// struct Point {
+///     x: f32,
+///     y: f32,
+///     tag: [u8; 4],
+/// }
+///
+/// derive_zeroable!(Point { x, y, tag });
+///
+/// let mut arena = Arena::new().unwrap();
+/// let point = arena.alloc_zeroed::<Point>().unwrap();
+/// assert_eq!((point.x, point.y, point.tag), (0.0, 0.0, [0; 4]));
+/// ```
+///
+/// References can't be zero, so this is rejected:
+///
+/// ```compile_fail
+/// use arena::derive_zeroable;
+///
+/// struct Borrowed {
+///     value: &'static u32,
+/// }
+///
+/// derive_zeroable!(Borrowed { value });
+/// ```
+#[cfg(feature = "zeroable-derive")]
+#[macro_export]
+macro_rules! derive_zeroable {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        const _: () = {
+            #[allow(dead_code)]
+            fn check(value: $ty) {
+                let $ty { $($field),* } = value;
+                $($crate::__assert_zeroable(&$field);)*
+            }
+        };
+        unsafe impl $crate::Zeroable for $ty {}
+    };
+    ($ty:ident ( $($field:ident),* $(,)? )) => {
+        const _: () = {
+            #[allow(dead_code)]
+            fn check(value: $ty) {
+                let $ty ( $($field),* ) = value;
+                $($crate::__assert_zeroable(&$field);)*
+            }
+        };
+        unsafe impl $crate::Zeroable for $ty {}
+    };
+}
+
+/// Order in which tracked destructors run on `reset`, `rewind_to` and drop.
+///
+/// `Lifo` mirrors how locals are dropped: a value may safely reference
+/// anything allocated before it. `Fifo` drops older values first, so a
+/// later value must never read an earlier one from its `Drop`, which is why
+/// selecting it through `ArenaConfig::drop_order` is unsafe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DropOrder {
+    /// newest first
+    #[default]
+    Lifo,
+
+    /// oldest first
+    Fifo,
+}
+
+#[must_use]
+pub struct ArenaSnapshot {
+    /// arena the snapshot was taken from
+    arena_uid: u64,
+
+    /// block that was active when the snapshot was taken
+    block_id: BlockId,
+
+    /// block's save point
+    offset: *mut u8,
+
+    /// save points of the non-empty blocks after it, the empty ones are
+    /// reset on rewind
+    later: Vec<(BlockId, *mut u8)>,
+
+    /// blocks with this id or higher were created after the snapshot
+    next_block_id: BlockId,
+
+    /// number of the first destructor registered after the snapshot
+    drop_seq: u64,
+
+    /// spilled allocations when the snapshot was taken
+    spills: usize,
+
+    /// tracked allocations when the snapshot was taken
+    #[cfg(feature = "stats")]
+    allocations: usize,
+}
+
+impl ArenaSnapshot {
+    /// Cursor `block`, at index `idx`, goes back to, `None` when it's left
+    /// alone. `block_idx` is where the snapshot's own block sits now.
+    fn cursor_for(&self, block_idx: usize, idx: usize, block: &Block) -> Option<*mut u8> {
+        if block.id == self.block_id {
+            Some(self.offset)
+        } else if block.id >= self.next_block_id || idx > block_idx {
+            let saved = self.later.iter().find(|(id, _)| *id == block.id);
+            Some(saved.map_or(block.start_ptr.as_ptr(), |&(_, cursor)| cursor))
+        } else {
+            None
+        }
+    }
+}
+
+#[must_use]
+pub struct FullSnapshot {
+    /// arena the snapshot was taken from
+    arena_uid: u64,
+
+    /// every block's save point
+    cursors: Vec<(BlockId, *mut u8)>,
+
+    /// number of the first destructor registered after the snapshot
+    drop_seq: u64,
+
+    /// spilled allocations when the snapshot was taken
+    spills: usize,
+
+    /// tracked allocations when the snapshot was taken
+    #[cfg(feature = "stats")]
+    allocations: usize,
+}
+
+#[cfg(feature = "stats")]
+struct AllocRecord {
+    ptr: *mut u8,
+    size: usize,
+
+    /// caller of the public alloc method
+    #[cfg(feature = "debug")]
+    location: &'static std::panic::Location<'static>,
+}
+
+struct DropThunk {
+    ptr: *mut u8,
+    len: usize,
+    drop: unsafe fn(*mut u8, usize),
+
+    /// registration number, increasing along `Arena::drops`
+    seq: u64,
+}
+
+impl DropThunk {
+    #[inline]
+    fn run(&self) {
+        unsafe { (self.drop)(self.ptr, self.len) }
+    }
+}
+
+fn run_in_order(thunks: &[DropThunk], order: DropOrder) {
+    match order {
+        DropOrder::Lifo => thunks.iter().rev().for_each(DropThunk::run),
+        DropOrder::Fifo => thunks.iter().for_each(DropThunk::run),
+    }
+}
+
+unsafe fn drop_thunk<T>(ptr: *mut u8, len: usize) {
+    unsafe { std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(ptr as *mut T, len)) }
+}
+
+type BlockId = usize;
+type BlockPtr = NonNull<u8>;
+type BlockSize = usize;
+type BlockCursor = Cell<*mut u8>;
+
+/// A block taken out of an arena by `Arena::into_parts`, its memory is
+/// released when dropped.
+pub struct OwnedBlock(Block);
+
+impl OwnedBlock {
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.0.id
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.0.size
+    }
+
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.0.used()
+    }
+
+    /// The used bytes `[start, cursor)`.
+    ///
+    /// # Safety
+    /// Every used byte must be initialized, padding included.
+    #[inline]
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        self.0.used_bytes()
+    }
+}
+
+const DEFAULT_BLOCK_SIZE: BlockSize = 64 * 1024;
+
+/// page granularity used by `BlockRounding::Page`
+pub const PAGE_SIZE: usize = 4096;
+
+/// base alignment of every block, one cache line
+const BLOCK_ALIGN: usize = 64;
+
+/// Strictest alignment accepted by the typed alloc methods. Every block kind
+/// can serve it, guarded blocks included since they're page aligned.
+pub const MAX_ALIGN: usize = PAGE_SIZE;
+
+/// Compile-time check that `T`'s alignment is at most `MAX_ALIGN`.
+#[inline(always)]
+pub(crate) const fn assert_align<T>() {
+    const {
+        assert!(
+            align_of::<T>() <= MAX_ALIGN,
+            "alignment exceeds arena::MAX_ALIGN"
+        )
+    };
+}
+
+#[repr(C)]
+struct Block {
+    id: BlockId,
+    start_ptr: BlockPtr,
+    end_ptr: BlockPtr,
+    curr_ptr: BlockCursor,
+    size: BlockSize,
+
+    /// alignment the block was allocated with, needed to free it
+    align: usize,
+
+    /// mapped with a trailing guard page instead of the global allocator
+    #[cfg(all(feature = "guard-pages", unix))]
+    guarded: bool,
+}
+
+impl Block {
+    #[cfg(test)]
+    pub fn new(size: BlockSize) -> Result<Self, ArenaError> {
+        Self::with_align(size, BLOCK_ALIGN)
+    }
+
+    pub fn with_align(size: BlockSize, align: usize) -> Result<Self, ArenaError> {
+        if size == 0 {
+            return Err(ArenaError::ZeroSize);
+        }
+
+        let layout = Layout::from_size_align(size, align)?;
+
+        unsafe {
+            let ptr = alloc(layout);
+            if ptr.is_null() {
+                Err(ArenaError::InsufficientMemory)
+            } else {
+                let mut block = Self::from_raw(NonNull::new_unchecked(ptr), size);
+                block.align = align;
+                Ok(block)
+            }
+        }
+    }
+
+    #[cfg(all(feature = "guard-pages", unix))]
+    pub fn new_guarded(size: BlockSize) -> Result<Self, ArenaError> {
+        if size == 0 {
+            return Err(ArenaError::ZeroSize);
+        }
+
+        let (ptr, usable) = unsafe { guard::map(size) }.ok_or(ArenaError::InsufficientMemory)?;
+        let mut block = unsafe { Self::from_raw(ptr, usable) };
+        block.guarded = true;
+
+        Ok(block)
+    }
+
+    #[inline]
+    fn with_config(
+        size: BlockSize,
+        align: usize,
+        config: &ArenaConfig,
+    ) -> Result<Self, ArenaError> {
+        let size = config
+            .rounding
+            .apply(size)
+            .ok_or(ArenaError::InsufficientMemory)?;
+
+        #[cfg(all(feature = "guard-pages", unix))]
+        if config.guard_pages {
+            return Self::new_guarded(size);
+        }
+
+        let _ = config;
+        Self::with_align(size, align)
+    }
+
+    /// # Safety
+    /// `start_ptr` must be valid for `size` bytes.
+    unsafe fn from_raw(start_ptr: BlockPtr, size: BlockSize) -> Self {
+        Self {
+            id: 0,
+            start_ptr,
+            end_ptr: unsafe { start_ptr.add(size) },
+            curr_ptr: BlockCursor::new(start_ptr.as_ptr()),
+            size,
+            align: BLOCK_ALIGN,
+            #[cfg(all(feature = "guard-pages", unix))]
+            guarded: false,
+        }
+    }
+
+    pub fn alloc(&self, layout: Layout) -> Result<*mut u8, ArenaError> {
+        let old_ptr = self.curr_ptr.get();
+
+        // all in usize, the pointer is only offset once the request is known
+        // to fit, so huge sizes can't wrap or step outside the block
+        let padding = self.padding_for(layout.align());
+        if !self.fits(layout) {
+            return Err(ArenaError::InsufficientMemory);
+        }
+
+        let aligned = unsafe { old_ptr.add(padding) };
+        self.curr_ptr.set(unsafe { aligned.add(layout.size()) });
+        Ok(aligned)
+    }
+
+    /// Bytes needed to bring the cursor up to `align`.
+    #[inline]
+    pub fn padding_for(&self, align: usize) -> usize {
+        (self.curr_ptr.get() as usize).wrapping_neg() & (align - 1)
+    }
+
+    /// Whether `ptr` points into the block's memory.
+    #[inline]
+    pub fn contains(&self, ptr: *mut u8) -> bool {
+        self.start_ptr.as_ptr() <= ptr && ptr < self.end_ptr.as_ptr()
+    }
+
+    /// Whether `layout` fits after the cursor, alignment padding included.
+    #[inline]
+    pub fn fits(&self, layout: Layout) -> bool {
+        self.padding_for(layout.align())
+            .checked_add(layout.size())
+            .is_some_and(|needed| needed <= self.remaining())
+    }
+
+    #[inline]
+    pub fn rewind_to(&mut self, save_point: *mut u8) {
+        self.curr_ptr.set(save_point);
+    }
+
+    #[inline]
+    pub fn reset(&mut self) {
+        self.curr_ptr.set(self.start_ptr.as_ptr());
+    }
+
+    #[inline]
+    pub fn reset_zeroed(&mut self) {
+        self.reset();
+        unsafe { std::ptr::write_bytes(self.start_ptr.as_ptr(), 0, self.size) };
+    }
+
+    #[inline]
+    pub fn used(&self) -> BlockSize {
+        (self.curr_ptr.get() as usize) - (self.start_ptr.as_ptr() as usize)
+    }
+
+    #[inline]
+    pub fn remaining(&self) -> BlockSize {
+        (self.end_ptr.as_ptr() as usize) - (self.curr_ptr.get() as usize)
+    }
+
+    #[inline]
+    pub fn used_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.start_ptr.as_ptr(), self.used()) }
+    }
+
+    #[cfg(test)]
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.start_ptr.as_ptr()
+    }
+
+    #[cfg(feature = "debug")]
     pub fn dump(&self, index: usize) {
         let start = self.start_ptr.as_ptr() as usize;
         let end = self.end_ptr.as_ptr() as usize;
         let curr = self.curr_ptr.get() as usize;
 
-        println!(
-            "  Block[{index}]: size = {:>6} bytes | used = {:>6} bytes | remaining = {:>6} bytes",
-            self.size,
-            curr - start,
-            end - curr
+        println!(
+            "  Block[{index}]: size = {:>6} bytes | used = {:>6} bytes | remaining = {:>6} bytes",
+            self.size,
+            curr - start,
+            end - curr
+        );
+        println!("    start = 0x{start:x}, curr = 0x{curr:x}, end = 0x{end:x}");
+    }
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        #[cfg(all(feature = "guard-pages", unix))]
+        if self.guarded {
+            unsafe { guard::unmap(self.start_ptr, self.size) };
+            return;
+        }
+
+        unsafe {
+            let layout = Layout::from_size_align_unchecked(self.size, self.align);
+            dealloc(self.start_ptr.as_ptr(), layout);
+        }
+    }
+}
+
+#[derive(Debug)]
+#[must_use]
+pub enum ArenaError {
+    /// Zero-size blocks are invalid
+    ZeroSize,
+
+    /// Block alignment must be power of two
+    BadAlignment,
+
+    /// OOM, couldn't allocate block
+    InsufficientMemory,
+
+    /// Fixed arena is full and isn't allowed to grow
+    CapacityExceeded,
+
+    /// Arena invariants don't hold
+    Corrupted,
+
+    /// The snapshot is from another arena, or its block was released since
+    /// it was taken
+    StaleSnapshot,
+
+    /// Bytes aren't valid UTF-8 past `valid_up_to`
+    InvalidUtf8 { valid_up_to: usize },
+
+    /// An iterator and its clone yielded different lengths
+    LengthMismatch,
+}
+
+impl Display for ArenaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArenaError::BadAlignment => {
+                f.write_str("Size should be non-zero and must be power of two.")
+            }
+            ArenaError::InsufficientMemory => f.write_str("Out of Memory."),
+            ArenaError::ZeroSize => write!(f, "Cannot allocate block of size zero"),
+            ArenaError::CapacityExceeded => f.write_str("Fixed arena capacity exceeded."),
+            ArenaError::Corrupted => f.write_str("Arena invariants violated."),
+            ArenaError::StaleSnapshot => {
+                f.write_str("Snapshot is from another arena or a released block.")
+            }
+            ArenaError::InvalidUtf8 { valid_up_to } => {
+                write!(f, "Invalid UTF-8 after byte {valid_up_to}.")
+            }
+            ArenaError::LengthMismatch => f.write_str("Iterator clone yielded a different length."),
+        }
+    }
+}
+
+impl From<LayoutError> for ArenaError {
+    fn from(_: LayoutError) -> Self {
+        ArenaError::BadAlignment
+    }
+}
+
+impl std::error::Error for ArenaError {}
+
+/// Memory handed back by `Arena::truncate_blocks` or `Arena::shrink_to_fit`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShrinkResult {
+    /// total size of the released blocks
+    pub freed_bytes: usize,
+
+    /// number of released blocks
+    pub freed_blocks: usize,
+}
+
+#[derive(Debug)]
+#[must_use]
+pub struct TryReserveError {
+    /// largest contiguous run the arena could provide before growing
+    available: usize,
+
+    /// whether a new block was requested from the system allocator
+    growth_attempted: bool,
+}
+
+impl TryReserveError {
+    pub fn available(&self) -> usize {
+        self.available
+    }
+
+    pub fn growth_attempted(&self) -> bool {
+        self.growth_attempted
+    }
+}
+
+impl Display for TryReserveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cannot reserve memory, only {} contiguous bytes available.",
+            self.available
+        )
+    }
+}
+
+impl std::error::Error for TryReserveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_have_low_bits_eq_0() {
+        let size = 32;
+        let mask = size - 1;
+
+        let block = Block::new(size).unwrap();
+
+        // the block address bitwise AND the alignment bits (size - 1) should
+        // be a mutually exclusive set of bits
+        assert!((block.as_ptr() as usize & mask) ^ mask == mask);
+    }
+
+    #[test]
+    fn test_alloc_str_concat() {
+        let mut arena = Arena::new().unwrap();
+
+        let s = arena.alloc_str_concat(&["foo", "/", "bar"]).unwrap();
+        assert_eq!(s, "foo/bar");
+        assert_eq!(s.len(), 7);
+    }
+
+    #[test]
+    fn test_try_reserve_grows() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+
+        arena.try_reserve(256).unwrap();
+        assert_eq!(arena.blocks.len(), 2);
+        assert!(arena.largest_available() >= 256);
+    }
+
+    #[test]
+    fn test_try_reserve_fixed_reports_available() {
+        let mut arena = Arena::fixed(64).unwrap();
+        arena.alloc([0u8; 24]).unwrap();
+
+        arena.try_reserve(40).unwrap();
+
+        let err = arena.try_reserve(41).unwrap_err();
+        assert_eq!(err.available(), 40);
+        assert!(!err.growth_attempted());
+        assert_eq!(arena.blocks.len(), 1);
+    }
+
+    struct DropRecorder(u32, std::rc::Rc<std::cell::RefCell<Vec<u32>>>);
+
+    impl Drop for DropRecorder {
+        fn drop(&mut self) {
+            self.1.borrow_mut().push(self.0);
+        }
+    }
+
+    fn record_drops(order: DropOrder) -> Vec<u32> {
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let config = unsafe { ArenaConfig::new().block_size(256).drop_order(order) };
+        let mut arena = Arena::with_config(config).unwrap();
+
+        for i in 0..3 {
+            arena.alloc_with_drop(DropRecorder(i, log.clone())).unwrap();
+        }
+        arena.reset();
+
+        log.take()
+    }
+
+    #[test]
+    fn test_drop_order_lifo() {
+        assert_eq!(record_drops(DropOrder::Lifo), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_drop_order_fifo() {
+        assert_eq!(record_drops(DropOrder::Fifo), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_scope_runs_only_inner_drops() {
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut arena = Arena::new().unwrap();
+        arena.alloc_with_drop(DropRecorder(0, log.clone())).unwrap();
+
+        arena.scope(|arena| {
+            arena.alloc_with_drop(DropRecorder(1, log.clone())).unwrap();
+            arena.scope(|arena| {
+                arena.alloc_with_drop(DropRecorder(2, log.clone())).unwrap();
+            });
+            assert_eq!(*log.borrow(), [2]);
+            arena.alloc_with_drop(DropRecorder(3, log.clone())).unwrap();
+        });
+        assert_eq!(*log.borrow(), [2, 3, 1]);
+
+        drop(arena);
+        assert_eq!(*log.borrow(), [2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn test_rewind_out_of_order_runs_reclaimed_drops() {
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut arena = Arena::with_block_size(256).unwrap();
+
+        let early = arena.snapshot();
+        arena.alloc([0u8; 64]).unwrap();
+        let late = arena.snapshot();
+        arena.rewind_to(early);
+
+        arena.alloc_with_drop(DropRecorder(0, log.clone())).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc_with_drop(DropRecorder(1, log.clone())).unwrap();
+
+        // 1 sits past `late`'s cursor and is handed back, 0 stays live
+        arena.rewind_to(late);
+        assert_eq!(*log.borrow(), [1]);
+
+        arena.copy_bytes(&[0xfe; 128]).unwrap();
+        arena.reset();
+        assert_eq!(*log.borrow(), [1, 0]);
+    }
+
+    #[test]
+    fn test_reset_zeroed_runs_drops() {
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut arena = Arena::new().unwrap();
+        arena.alloc_with_drop(DropRecorder(0, log.clone())).unwrap();
+        arena.alloc_with_drop(DropRecorder(1, log.clone())).unwrap();
+
+        arena.reset_zeroed();
+        assert_eq!(*log.borrow(), [1, 0]);
+        drop(arena);
+        assert_eq!(log.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_alloc_with_drop_frees_heap_values() {
+        let value = std::rc::Rc::new(());
+        let mut arena = Arena::new().unwrap();
+        arena
+            .alloc_with_drop(vec![value.clone(), value.clone()])
+            .unwrap();
+        arena.alloc_with_drop(Box::new(value.clone())).unwrap();
+        arena.alloc_with_drop(String::from("owned")).unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&value), 4);
+
+        // plain `alloc` stays the no-drop path, its clone leaks
+        arena.alloc(value.clone()).unwrap();
+        arena.reset();
+        assert_eq!(std::rc::Rc::strong_count(&value), 2);
+    }
+
+    #[test]
+    fn test_shrink_last_reclaims_tail() {
+        let mut arena = Arena::with_block_size(256).unwrap();
+        let before = arena.largest_available();
+
+        let slice = arena.alloc_slice::<u32>(8).unwrap() as *mut [u32];
+        let slice = unsafe { arena.shrink_last(&mut *slice, 3) };
+        assert_eq!(slice.len(), 3);
+
+        assert_eq!(arena.largest_available(), before - 3 * size_of::<u32>());
+    }
+
+    #[test]
+    fn test_shrink_last_not_most_recent() {
+        let mut arena = Arena::with_block_size(256).unwrap();
+
+        let slice = arena.alloc_slice::<u32>(8).unwrap() as *mut [u32];
+        arena.alloc(0u32).unwrap();
+        let available = arena.largest_available();
+
+        let slice = unsafe { arena.shrink_last(&mut *slice, 3) };
+        assert_eq!(slice.len(), 3);
+        assert_eq!(arena.largest_available(), available);
+    }
+
+    #[test]
+    fn test_reset_promotes_largest_block() {
+        let config = ArenaConfig::new().block_size(64).promote_largest(true);
+        let mut arena = Arena::with_config(config).unwrap();
+
+        arena.alloc([0u8; 48]).unwrap();
+        arena.alloc([0u8; 512]).unwrap();
+        arena.alloc([0u8; 48]).unwrap();
+        assert_eq!(arena.blocks.len(), 3);
+
+        let largest_id = arena.blocks[1].get_mut().id;
+        arena.reset();
+
+        let ids: Vec<_> = arena.blocks.iter_mut().map(|b| b.get_mut().id).collect();
+        assert_eq!(ids, vec![largest_id, 0, 2]);
+
+        let ptr = arena.alloc([0u8; 256]).unwrap().as_ptr();
+        assert_eq!(ptr, arena.blocks[0].get_mut().start_ptr.as_ptr());
+        assert_eq!(arena.blocks.len(), 3);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_size_class_histogram() {
+        let mut arena = Arena::new().unwrap();
+
+        arena.alloc(0u8).unwrap();
+        arena.alloc(0u8).unwrap();
+        arena.alloc([0u8; 3]).unwrap();
+        arena.alloc([0u8; 100]).unwrap();
+        arena.alloc([0u8; 128]).unwrap();
+        arena.alloc([0u8; 4000]).unwrap();
+
+        let histogram = arena.size_class_histogram();
+        let expected = [(1, 2), (4, 1), (128, 2), (4096, 1)];
+        assert!(histogram.into_iter().eq(expected));
+
+        arena.reset();
+        assert!(arena.size_class_histogram().is_empty());
+    }
+
+    #[test]
+    fn test_align_to_reports_padding() {
+        let mut arena = Arena::with_block_size(256).unwrap();
+        arena.alloc([0u8; 5]).unwrap();
+
+        let curr = arena.current_block().curr_ptr.get() as usize;
+        let gap = curr.next_multiple_of(64) - curr;
+
+        assert_eq!(arena.align_to(64).unwrap(), gap);
+        assert_eq!(arena.current_block().curr_ptr.get() as usize % 64, 0);
+        assert_eq!(arena.align_to(64).unwrap(), 0);
+        assert!(matches!(arena.align_to(3), Err(ArenaError::BadAlignment)));
+    }
+
+    #[test]
+    fn test_bytes_since_across_blocks() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 16]).unwrap();
+
+        let snapshot = arena.snapshot();
+        assert_eq!(arena.bytes_since(&snapshot), 0);
+
+        arena.alloc([0u8; 40]).unwrap();
+        arena.alloc([0u8; 32]).unwrap();
+        assert_eq!(arena.blocks.len(), 2);
+        assert_eq!(arena.bytes_since(&snapshot), 72);
+
+        arena.rewind_to(snapshot);
+    }
+
+    #[test]
+    fn test_for_each_block_mut_xor() {
+        const KEY: u8 = 0x5a;
+        let mut arena = Arena::with_block_size(64).unwrap();
+
+        arena.copy_slice(&[1u8; 48]).unwrap();
+        arena.copy_slice(&[2u8; 40]).unwrap();
+
+        let mut seen = 0;
+        unsafe {
+            arena.for_each_block_mut(|bytes| {
+                seen += bytes.len();
+                bytes.iter_mut().for_each(|b| *b ^= KEY);
+            })
+        };
+        assert_eq!(seen, 88);
+
+        let mut blocks = Vec::new();
+        unsafe { arena.for_each_block_mut(|bytes| blocks.push(bytes.to_vec())) };
+        assert_eq!(blocks, vec![vec![1 ^ KEY; 48], vec![2 ^ KEY; 40]]);
+    }
+
+    #[test]
+    fn test_rewind_all_restores_every_block() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 16]).unwrap();
+        arena.alloc([0u8; 56]).unwrap();
+        assert_eq!(arena.blocks.len(), 2);
+
+        let cursor = |arena: &mut Arena, i: usize| arena.blocks[i].get_mut().curr_ptr.get();
+        let before = [cursor(&mut arena, 0), cursor(&mut arena, 1)];
+        let snapshot = arena.snapshot_all();
+
+        // first-fit puts this one back into block 0
+        arena.alloc([0u8; 32]).unwrap();
+        arena.alloc([0u8; 100]).unwrap();
+        assert_ne!(cursor(&mut arena, 0), before[0]);
+        assert_eq!(arena.blocks.len(), 3);
+
+        arena.rewind_all(snapshot).unwrap();
+        assert_eq!([cursor(&mut arena, 0), cursor(&mut arena, 1)], before);
+        assert_eq!(arena.blocks[2].get_mut().used(), 0);
+    }
+
+    #[test]
+    fn test_rewind_all_rejects_foreign_or_out_of_bounds_snapshot() {
+        let other = Arena::with_block_size(64).unwrap();
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 16]).unwrap();
+        let used = arena.used();
+
+        let foreign = other.snapshot_all();
+        assert!(matches!(
+            arena.rewind_all(foreign),
+            Err(ArenaError::StaleSnapshot)
+        ));
+        assert_eq!(arena.used(), used);
+
+        let mut snapshot = arena.snapshot_all();
+        let end = arena.blocks[0].get_mut().end_ptr.as_ptr();
+        snapshot.cursors[0].1 = end.wrapping_add(1);
+        assert!(matches!(
+            arena.rewind_all(snapshot),
+            Err(ArenaError::StaleSnapshot)
+        ));
+        assert_eq!(arena.used(), used);
+    }
+
+    #[test]
+    fn test_alloc_atomic_is_aligned() {
+        use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+        let mut arena = Arena::new().unwrap();
+        arena.alloc(1u8).unwrap();
+
+        let counter = arena.alloc_atomic(AtomicU64::new(1)).unwrap();
+        assert_eq!(
+            counter as *const AtomicU64 as usize % align_of::<AtomicU64>(),
+            0
+        );
+        counter.fetch_add(41, Ordering::SeqCst);
+        assert_eq!(counter.load(Ordering::SeqCst), 42);
+
+        arena.alloc(1u8).unwrap();
+
+        let flags = arena.alloc_atomic(AtomicUsize::new(0b01)).unwrap();
+        assert_eq!(
+            flags as *const AtomicUsize as usize % align_of::<AtomicUsize>(),
+            0
+        );
+        flags.fetch_or(0b10, Ordering::Relaxed);
+        assert_eq!(flags.swap(0, Ordering::Relaxed), 0b11);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_new_block_event() {
+        use std::sync::{Arc, Mutex};
+        use tracing::{
+            Event, Metadata, Subscriber,
+            field::{Field, Visit},
+            span::{Attributes, Id, Record},
+        };
+
+        #[derive(Default)]
+        struct Capture(Arc<Mutex<Vec<String>>>);
+
+        struct Message<'a>(&'a mut String);
+
+        impl Visit for Message<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    *self.0 = format!("{value:?}");
+                }
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut message = String::new();
+                event.record(&mut Message(&mut message));
+                self.0.lock().unwrap().push(message);
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = Capture(events.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut arena = Arena::with_block_size(64).unwrap();
+            arena.alloc([0u8; 48]).unwrap();
+            arena.alloc([0u8; 48]).unwrap();
+        });
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.iter().filter(|e| *e == "arena new block").count(), 1);
+    }
+
+    #[test]
+    fn test_alloc_slice_cell_shared_mutation() {
+        let mut arena = Arena::new().unwrap();
+        let cells = arena.alloc_slice_cell::<u32>(4).unwrap();
+
+        let (a, b) = (&cells[1], &cells[1]);
+        a.set(7);
+        b.set(b.get() * 6);
+
+        assert_eq!(
+            cells.iter().map(Cell::get).collect::<Vec<_>>(),
+            [0, 42, 0, 0]
+        );
+    }
+
+    fn assert_accounting(arena: &Arena, used: usize, capacity: usize) {
+        assert_eq!(arena.used(), used);
+        assert_eq!(arena.capacity(), capacity);
+        assert_eq!(arena.used() + arena.remaining(), arena.capacity());
+    }
+
+    #[test]
+    fn test_accounting_empty() {
+        let arena = Arena::with_block_size(64).unwrap();
+        assert_accounting(&arena, 0, 64);
+    }
+
+    #[test]
+    fn test_accounting_partial_block() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 10]).unwrap();
+        assert_accounting(&arena, 10, 64);
+    }
+
+    #[test]
+    fn test_accounting_full_blocks() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        for _ in 0..3 {
+            arena.alloc([0u8; 64]).unwrap();
+        }
+
+        assert_eq!(arena.blocks.len(), 3);
+        assert_accounting(&arena, 192, 192);
+        assert!(
+            arena
+                .blocks
+                .iter()
+                .all(|b| unsafe { &*b.get() }.remaining() == 0)
+        );
+    }
+
+    #[test]
+    fn test_accounting_oversized_block() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.try_reserve(1000).unwrap();
+        arena.alloc([0u8; 100]).unwrap();
+
+        assert_eq!(arena.blocks.len(), 2);
+        assert_accounting(&arena, 164, 1064);
+    }
+
+    #[test]
+    fn test_accounting_after_reset() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 40]).unwrap();
+        arena.alloc([0u8; 300]).unwrap();
+        arena.reset();
+
+        assert_accounting(&arena, 0, 364);
+    }
+
+    #[test]
+    fn test_leak_static_reference() {
+        let arena: &'static mut Arena = Box::leak(Box::new(Arena::new().unwrap()));
+
+        let config: &'static mut (u32, &str) = unsafe { arena.leak((8080, "localhost")) }.unwrap();
+        config.0 += 1;
+
+        let handle = std::thread::spawn(move || *config);
+        assert_eq!(handle.join().unwrap(), (8081, "localhost"));
+    }
+
+    #[test]
+    fn test_effective_block_size_rounding() {
+        let config = ArenaConfig::new().block_size(100_000);
+        let arena = Arena::with_config(config).unwrap();
+        assert_eq!(arena.effective_block_size(), 100_000);
+
+        let arena = Arena::with_config(config.rounding(BlockRounding::PowerOfTwo)).unwrap();
+        assert_eq!(arena.effective_block_size(), 131_072);
+        assert_eq!(arena.capacity(), 131_072);
+
+        let mut arena = Arena::with_config(config.rounding(BlockRounding::Page)).unwrap();
+        assert_eq!(arena.effective_block_size(), 102_400);
+
+        arena.alloc([0u8; 102_400]).unwrap();
+        arena.alloc([0u8; 200_000]).unwrap();
+        assert_eq!(arena.capacity(), 102_400 + 200_704);
+    }
+
+    struct DropCounter(std::rc::Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_alloc_slice_cell_drops_on_reset() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::new().unwrap();
+
+        let cells = arena.alloc_slice_cell::<Option<DropCounter>>(3).unwrap();
+        for cell in &cells[..2] {
+            cell.set(Some(DropCounter(drops.clone())));
+        }
+        assert_eq!(drops.get(), 0);
+
+        arena.reset();
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_alloc_slice_from_fn_drops_each_element_once() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::new().unwrap();
+
+        let slice = arena
+            .alloc_slice_from_fn(5, |_| DropCounter(drops.clone()))
+            .unwrap();
+        assert_eq!(slice.len(), 5);
+        assert_eq!(drops.get(), 0);
+
+        arena.reset();
+        assert_eq!(drops.get(), 5);
+
+        drop(arena);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn test_alloc_slice_from_iter() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::new().unwrap();
+
+        let squares = arena.alloc_slice_from_iter((0..4).map(|i| i * i)).unwrap();
+        assert_eq!(squares, &[0, 1, 4, 9]);
+
+        let counters = (0..3).map(|_| DropCounter(drops.clone()));
+        arena.alloc_slice_from_iter(counters).unwrap();
+        drop(arena);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_shrink_last_drops_tracked_tail() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::new().unwrap();
+
+        let slice = arena
+            .alloc_slice_from_fn(4, |_| DropCounter(drops.clone()))
+            .unwrap() as *mut [DropCounter];
+        unsafe { arena.shrink_last(&mut *slice, 1) };
+        assert_eq!(drops.get(), 3);
+
+        arena.reset();
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn test_new_block_aligned_to_request() {
+        #[repr(C, align(4096))]
+        struct Page([u8; 4096]);
+
+        let mut arena = Arena::with_block_size(4096).unwrap();
+        arena.alloc(1u8).unwrap();
+
+        let page = arena.alloc(Page([3; 4096])).unwrap() as *mut Page as *mut u8;
+        assert_eq!(page as usize % 4096, 0);
+        assert!(unsafe { (*page.cast::<Page>()).0.iter().all(|&b| b == 3) });
+
+        assert_eq!(arena.blocks.len(), 2);
+        assert_eq!(arena.blocks[1].get_mut().as_ptr(), page);
+        assert_eq!(arena.blocks[1].get_mut().remaining(), 0);
+    }
+
+    #[test]
+    fn test_utilization() {
+        let mut arena = Arena::with_block_size(1024).unwrap();
+        assert_eq!(arena.utilization(), 0.0);
+        assert_eq!(arena.block_utilization(), vec![0.0]);
+
+        arena.alloc([0u8; 512]).unwrap();
+        assert!((arena.utilization() - 0.5).abs() < f64::EPSILON);
+
+        arena.alloc([0u8; 1024]).unwrap();
+        assert_eq!(arena.block_utilization(), vec![0.5, 1.0]);
+        assert!((arena.utilization() - 0.75).abs() < f64::EPSILON);
+        assert_eq!(ratio(0, 0), 0.0);
+    }
+
+    #[test]
+    fn test_alloc_and_copy_bytes() {
+        let mut arena = Arena::new().unwrap();
+
+        let zeroed = arena.alloc_bytes(16).unwrap();
+        assert_eq!(zeroed, &[0; 16]);
+        zeroed[3] = 1;
+
+        let data = b"arena bytes";
+        let fast = arena.copy_bytes(data).unwrap().to_vec();
+        let generic = arena.copy_slice(data).unwrap().to_vec();
+        assert_eq!(fast, generic);
+        assert_eq!(fast, data);
+    }
+
+    #[test]
+    fn test_alloc_bytes_empty() {
+        let mut arena = Arena::new().unwrap();
+
+        assert!(arena.alloc_bytes(0).unwrap().is_empty());
+        assert!(arena.copy_bytes(&[]).unwrap().is_empty());
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    fn test_zero_slices_toggle() {
+        let dirty = |config: ArenaConfig| {
+            let mut arena = Arena::with_config(config.block_size(64)).unwrap();
+            arena.copy_bytes(&[0xaa; 32]).unwrap();
+            arena.reset();
+            arena.alloc_slice::<u8>(32).unwrap().to_vec()
+        };
+
+        assert_eq!(dirty(ArenaConfig::new()), [0; 32]);
+
+        let config = unsafe { ArenaConfig::new().zero_slices(false) };
+        assert_eq!(dirty(config), [0xaa; 32]);
+    }
+
+    #[test]
+    fn test_snapshot_uses_active_block_under_best_fit() {
+        let config = ArenaConfig::new().block_size(64).fit(BlockFit::BestFit);
+        let mut arena = Arena::with_config(config).unwrap();
+
+        arena.alloc([0u8; 48]).unwrap();
+        arena.try_reserve(1000).unwrap();
+        arena.alloc([0u8; 4]).unwrap();
+
+        // best-fit picked the tighter first block, not the last one
+        assert_eq!(arena.active, 0);
+        let snapshot = arena.snapshot();
+        let cursor = arena.blocks[0].get_mut().curr_ptr.get();
+        let last_cursor = arena.blocks[1].get_mut().curr_ptr.get();
+
+        arena.alloc([0u8; 8]).unwrap();
+        arena.alloc([0u8; 200]).unwrap();
+        assert_eq!(arena.active, 1);
+        assert_eq!(arena.bytes_since(&snapshot), 208);
+
+        arena.rewind_to(snapshot);
+        assert_eq!(arena.blocks[0].get_mut().curr_ptr.get(), cursor);
+        assert_eq!(arena.active, 0);
+
+        // blocks after the active one are covered too
+        assert_eq!(arena.blocks[1].get_mut().curr_ptr.get(), last_cursor);
+    }
+
+    #[test]
+    fn test_scope_in_reused_arena_reclaims_later_blocks() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        for _ in 0..3 {
+            arena.alloc([0u8; 64]).unwrap();
+        }
+        arena.reset();
+        arena.alloc([0u8; 8]).unwrap();
+
+        let mut later = Arena::with_block_size(64).unwrap();
+        later.alloc([0u8; 16]).unwrap();
+        later.alloc([0u8; 56]).unwrap();
+        later.alloc([0u8; 8]).unwrap();
+        assert_eq!(later.active, 0);
+
+        for arena in [&mut arena, &mut later] {
+            let used = arena.used();
+            arena.scope(|arena| {
+                arena.alloc([0u8; 48]).unwrap();
+                arena.alloc([0u8; 48]).unwrap();
+                arena.alloc([0u8; 40]).unwrap();
+            });
+            assert_eq!(arena.used(), used);
+        }
+    }
+
+    #[test]
+    fn test_rewind_resets_blocks_created_after_snapshot() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 32]).unwrap();
+
+        let snapshot = arena.snapshot();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        assert_eq!(arena.used(), 160);
+
+        arena.rewind_to(snapshot);
+        assert_eq!(arena.used(), 32);
+    }
+
+    #[cfg(feature = "timing")]
+    #[test]
+    fn test_on_reset_reports_duration() {
+        let timings = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut arena = Arena::with_block_size(4096).unwrap();
+
+        let sink = timings.clone();
+        arena.on_reset(move |elapsed| sink.borrow_mut().push(elapsed));
+
+        for _ in 0..4 {
+            arena.alloc([0u8; 4096]).unwrap();
+        }
+        arena.reset_zeroed();
+        arena.reset();
+
+        let timings = timings.borrow();
+        assert_eq!(timings.len(), 2);
+        assert!(timings[0] > std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_forget_drops_after_moving_out() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::new().unwrap();
+
+        let slice = arena
+            .alloc_slice_from_fn(3, |_| DropCounter(drops.clone()))
+            .unwrap();
+        let moved: Vec<_> = slice.iter().map(|c| unsafe { std::ptr::read(c) }).collect();
+
+        arena.forget_drops();
+        arena.reset();
+        assert_eq!(drops.get(), 0);
+
+        drop(moved);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_forget_drops_since_snapshot() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::new().unwrap();
+
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        let snapshot = arena.snapshot();
+
+        let moved =
+            unsafe { std::ptr::read(arena.alloc_with_drop(DropCounter(drops.clone())).unwrap()) };
+        arena.forget_drops_since(&snapshot);
+        arena.rewind_to(snapshot);
+        assert_eq!(drops.get(), 0);
+
+        drop(moved);
+        drop(arena);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_drops_registered_after_forget_still_run() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::new().unwrap();
+
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        let snapshot = arena.snapshot();
+        arena.forget_drops();
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        arena.rewind_to(snapshot);
+        assert_eq!(drops.get(), 1);
+
+        let early = arena.snapshot();
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        let late = arena.snapshot();
+        arena.forget_drops_since(&early);
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        arena.rewind_to(late);
+        assert_eq!(drops.get(), 2);
+
+        arena.copy_bytes(&[0xde; 64]).unwrap();
+        arena.reset();
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_alloc_union() {
+        #[repr(C)]
+        union Value {
+            int: u64,
+            float: f32,
+            bytes: [u8; 2],
+        }
+
+        let mut arena = Arena::new().unwrap();
+        arena.alloc(1u8).unwrap();
+
+        let slot = arena.alloc_union::<Value>().unwrap();
+        assert_eq!(slot.as_ptr() as usize % align_of::<Value>(), 0);
+
+        let value = slot.write(Value { float: 1.5 });
+        assert_eq!(unsafe { value.float }, 1.5);
+
+        value.int = u64::MAX;
+        assert_eq!(unsafe { value.bytes }, [0xff, 0xff]);
+        assert_eq!(size_of::<Value>(), 8);
+    }
+
+    #[test]
+    fn test_block_alloc_huge_size_errors() {
+        let block = Block::new(64).unwrap();
+        let layout = Layout::from_size_align(isize::MAX as usize - 64, 1).unwrap();
+
+        assert!(matches!(
+            block.alloc(layout),
+            Err(ArenaError::InsufficientMemory)
+        ));
+        assert_eq!(block.used(), 0);
+    }
+
+    /// Needs 5 GiB of address space, pages are only touched around the
+    /// 4 GiB boundary.
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    #[ignore]
+    fn test_block_larger_than_4gib() {
+        const GIB: usize = 1 << 30;
+
+        let mut arena = Arena::with_block_size(5 * GIB).unwrap();
+        assert_eq!(arena.capacity(), 5 * GIB);
+
+        let below = Layout::from_size_align(4 * GIB - 4, 1).unwrap();
+        arena.try_alloc(below).unwrap();
+
+        let straddling = arena.alloc(u64::MAX).unwrap() as *mut u64 as usize;
+        let start = arena.blocks[0].get_mut().start_ptr.as_ptr() as usize;
+        assert_eq!(straddling - start, 4 * GIB);
+
+        assert_eq!(arena.used(), 4 * GIB + 8);
+        assert_eq!(arena.remaining(), GIB - 8);
+        assert_eq!(arena.blocks.len(), 1);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_regions_in_allocation_order() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+
+        arena.alloc(0x0102_0304u32).unwrap();
+        arena.alloc_str("hello").unwrap();
+        arena.copy_bytes(&[9; 60]).unwrap();
+
+        let regions: Vec<_> = unsafe { arena.regions() }.collect();
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions[0], 0x0102_0304u32.to_ne_bytes());
+        assert_eq!(regions[1], b"hello");
+        assert_eq!(regions[2], &[9; 60]);
+
+        let slice = arena.alloc_slice::<u8>(8).unwrap() as *mut [u8];
+        unsafe { arena.shrink_last(&mut *slice, 2) };
+        assert_eq!(unsafe { arena.regions() }.last().unwrap().len(), 2);
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn test_allocation_location_captured() {
+        let mut arena = Arena::new().unwrap();
+
+        let line = line!() + 1;
+        arena.alloc(1u64).unwrap();
+        arena.alloc_str("where").unwrap();
+
+        let location = arena.allocations[0].location;
+        assert_eq!(location.file(), file!());
+        assert_eq!(location.line(), line);
+        assert_eq!(arena.allocations[1].location.line(), line + 1);
+    }
+
+    #[test]
+    fn test_reserve_aligned_packs_without_new_block() {
+        #[repr(C, align(64))]
+        struct Line([u8; 64]);
+
+        let mut arena = Arena::with_block_size(256).unwrap();
+        arena.alloc(1u8).unwrap();
+
+        arena.reserve_aligned(128, 64).unwrap();
+        assert_eq!(arena.blocks.len(), 1);
+
+        for _ in 0..2 {
+            let line = arena.alloc(Line([0; 64])).unwrap();
+            assert_eq!(line as *mut Line as usize % 64, 0);
+        }
+        assert_eq!(arena.blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_reserve_aligned_adds_block() {
+        let mut arena = Arena::with_block_size(256).unwrap();
+        arena.alloc([0u8; 200]).unwrap();
+
+        arena.reserve_aligned(128, 64).unwrap();
+        assert_eq!(arena.blocks.len(), 2);
+        assert!(
+            arena.blocks[1]
+                .get_mut()
+                .fits(Layout::from_size_align(128, 64).unwrap())
         );
-        println!("    start = 0x{start:x}, curr = 0x{curr:x}, end = 0x{end:x}");
+
+        arena.alloc([0u8; 256]).unwrap();
+        arena.reserve_aligned(300, 64).unwrap();
+        assert_eq!(arena.blocks.last_mut().unwrap().get_mut().size, 300);
+
+        let mut fixed = Arena::fixed(256).unwrap();
+        fixed.alloc([0u8; 200]).unwrap();
+        assert!(matches!(
+            fixed.reserve_aligned(128, 64),
+            Err(ArenaError::CapacityExceeded)
+        ));
+        assert!(matches!(
+            fixed.reserve_aligned(8, 3),
+            Err(ArenaError::BadAlignment)
+        ));
     }
-}
 
-impl Drop for Block {
-    fn drop(&mut self) {
+    #[test]
+    fn test_into_parts_round_trip() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.copy_bytes(b"first block").unwrap();
+        arena.copy_bytes(&[7; 64]).unwrap();
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        let used = arena.used();
+
+        let (blocks, config) = arena.into_parts();
+        assert_eq!(drops.get(), 1);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(unsafe { &blocks[0].as_bytes()[..11] }, b"first block");
+        assert_eq!(unsafe { blocks[1].as_bytes() }, &[7; 64]);
+
+        let mut arena = Arena::from_parts(blocks, config).unwrap();
+        assert!(arena.verify().is_ok());
+        assert_eq!(arena.used(), used);
+
+        assert_eq!(arena.alloc_str("again").unwrap(), "again");
+        arena.alloc([0u8; 100]).unwrap();
+        assert_eq!(arena.blocks.len(), 3);
+        assert!(arena.verify().is_ok());
+    }
+
+    #[test]
+    fn test_from_parts_keeps_block_ids() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+
+        let (blocks, config) = arena.into_parts();
+        let ids: Vec<_> = blocks.iter().map(OwnedBlock::id).collect();
+        assert_eq!(ids, [0, 1]);
+
+        let mut arena = Arena::from_parts(blocks.into_iter().rev().collect(), config).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        let ids: Vec<_> = arena.blocks.iter_mut().map(|b| b.get_mut().id).collect();
+        assert_eq!(ids, [1, 0, 2]);
+    }
+
+    #[test]
+    fn test_rewind_after_from_parts_never_corrupts() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 16]).unwrap();
+        let snapshot = arena.snapshot();
+
+        arena.truncate_blocks(1);
+        let (blocks, config) = arena.into_parts();
+        let mut arena = Arena::from_parts(blocks, config).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+
+        // block id 2 is handed out again, but the snapshot's cursor is only
+        // accepted if it lies inside that block
+        let _ = arena.try_rewind_to(snapshot);
+        assert!(arena.verify().is_ok());
+    }
+
+    #[test]
+    fn test_try_rewind_to_rejects_offset_outside_block() {
+        let mut other = Arena::new().unwrap();
+        other.alloc([0u8; 16]).unwrap();
+        let foreign = other.snapshot();
+
+        // same block id, different memory
+        let mut arena = Arena::new().unwrap();
+        arena.alloc([0u8; 16]).unwrap();
+        let used = arena.used();
+        assert!(matches!(
+            arena.try_rewind_to(foreign),
+            Err(ArenaError::StaleSnapshot)
+        ));
+        assert_eq!(arena.used(), used);
+    }
+
+    #[test]
+    fn test_from_parts_rejects_no_blocks() {
+        let result = Arena::from_parts(Vec::new(), ArenaConfig::new());
+        assert!(matches!(result, Err(ArenaError::Corrupted)));
+    }
+
+    #[test]
+    fn test_alloc_slice_zeroed() {
+        let mut arena = Arena::new().unwrap();
+        arena.copy_bytes(&[0xff; 64]).unwrap();
+        arena.reset();
+
+        let values = arena.alloc_slice_zeroed::<u64>(8).unwrap();
+        assert_eq!(values, &[0; 8]);
+
+        let ptr = arena.alloc_zeroed::<Option<NonNull<u8>>>().unwrap();
+        assert!(ptr.is_none());
+    }
+
+    #[cfg(feature = "zeroable-derive")]
+    #[test]
+    fn test_derive_zeroable() {
+        struct Pod {
+            id: u32,
+            pos: [f32; 3],
+            flags: Cell<u8>,
+        }
+        derive_zeroable!(Pod { id, pos, flags });
+
+        struct Pair(u16, i64);
+        derive_zeroable!(Pair(a, b));
+
+        let mut arena = Arena::new().unwrap();
+        let pods = arena.alloc_slice_zeroed::<Pod>(4).unwrap();
+        assert!(
+            pods.iter()
+                .all(|p| p.id == 0 && p.pos == [0.0; 3] && p.flags.get() == 0)
+        );
+
+        let pair = arena.alloc_zeroed::<Pair>().unwrap();
+        assert_eq!((pair.0, pair.1), (0, 0));
+    }
+
+    #[test]
+    fn test_prefetch_then_alloc() {
+        let mut arena = Arena::with_block_size(16 * 1024).unwrap();
+        arena.prefetch(0);
+        arena.prefetch(usize::MAX);
+
+        arena.prefetch(8 * 1024);
+        let used = arena.used();
+        let values = arena.alloc_slice_from_fn(1024, |i| i as u64).unwrap();
+        assert_eq!(values[1023], 1023);
+        assert_eq!(arena.used() - used, 8 * 1024);
+    }
+
+    #[test]
+    fn test_alloc_ptr() {
+        let mut arena = Arena::new().unwrap();
+        arena.alloc(1u8).unwrap();
+
+        let value = arena.alloc_ptr(0xdead_beef_u64).unwrap();
+        assert_eq!(value.as_ptr() as usize % align_of::<u64>(), 0);
+
+        let slice = arena.alloc_slice_ptr::<u32>(4).unwrap();
+        assert_eq!(slice.len(), 4);
+        assert_eq!(slice.cast::<u32>().as_ptr() as usize % align_of::<u32>(), 0);
+
         unsafe {
-            let layout = Layout::from_size_align_unchecked(self.size, align_of::<u8>());
-            dealloc(self.start_ptr.as_ptr(), layout);
+            assert_eq!(*value.as_ptr(), 0xdead_beef);
+            let elems = slice.cast::<u32>();
+            for i in 0..4 {
+                elems.add(i).write(i as u32 * 10);
+            }
+            assert_eq!(&*slice.as_ptr(), &[0, 10, 20, 30]);
         }
     }
-}
 
-#[derive(Debug)]
-#[must_use]
-pub enum ArenaError {
-    /// Zero-size blocks are invalid
-    ZeroSize,
+    #[test]
+    fn test_on_overflow_grow() {
+        let mut arena = Arena::with_config(
+            ArenaConfig::new()
+                .block_size(64)
+                .on_overflow(OnOverflow::Grow),
+        )
+        .unwrap();
+        arena.alloc([1u8; 48]).unwrap();
+        arena.alloc([2u8; 48]).unwrap();
+        assert_eq!(arena.blocks.len(), 2);
+        assert_eq!(arena.used(), 96);
+    }
 
-    /// Block alignment must be power of two
-    BadAlignment,
+    #[test]
+    fn test_on_overflow_error() {
+        let mut arena = Arena::with_config(
+            ArenaConfig::new()
+                .block_size(64)
+                .on_overflow(OnOverflow::Error),
+        )
+        .unwrap();
+        arena.alloc([1u8; 48]).unwrap();
+        assert!(matches!(
+            arena.alloc([2u8; 48]),
+            Err(ArenaError::CapacityExceeded)
+        ));
+        assert_eq!(arena.blocks.len(), 1);
+        assert_eq!(arena.used(), 48);
+    }
 
-    /// OOM, couldn't allocate block
-    InsufficientMemory,
-}
+    #[test]
+    fn test_on_overflow_spill() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::with_config(
+            ArenaConfig::new()
+                .block_size(64)
+                .on_overflow(OnOverflow::Spill),
+        )
+        .unwrap();
+        arena.alloc([1u8; 48]).unwrap();
 
-impl Display for ArenaError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ArenaError::BadAlignment => {
-                f.write_str("Size should be non-zero and must be power of two.")
+        let spilled = arena.alloc([2u8; 48]).unwrap();
+        assert_eq!(spilled, &[2; 48]);
+        assert_eq!(arena.blocks.len(), 1);
+        assert_eq!(arena.used(), 48);
+        assert_eq!(arena.spills.len(), 1);
+
+        let snapshot = arena.snapshot();
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        arena
+            .alloc_with_drop([DropCounter(drops.clone()), DropCounter(drops.clone())])
+            .unwrap();
+        assert_eq!(arena.spills.len(), 2);
+
+        arena.rewind_to(snapshot);
+        assert_eq!(drops.get(), 3);
+        assert_eq!(arena.spills.len(), 1);
+
+        arena.reset();
+        assert!(arena.spills.is_empty());
+        assert_eq!(arena.alloc([3u8; 64]).unwrap(), &[3; 64]);
+        assert!(arena.spills.is_empty());
+    }
+
+    #[test]
+    fn test_copy_from_iter_of_slices_exact() {
+        let mut arena = Arena::new().unwrap();
+        let chunks: [&[u16]; 3] = [&[1, 2], &[], &[3, 4, 5]];
+
+        let used = arena.used();
+        let joined = arena.copy_from_iter_of_slices_exact(chunks).unwrap();
+        assert_eq!(joined, &[1, 2, 3, 4, 5]);
+        assert_eq!(arena.used() - used, 5 * size_of::<u16>());
+    }
+
+    #[test]
+    fn test_copy_from_iter_of_slices_exact_rejects_unstable_clone() {
+        // clones report 1-byte chunks, so sizing undercounts the copy
+        struct Liar<'s> {
+            chunks: std::slice::Iter<'s, &'s [u8]>,
+            cloned: bool,
+        }
+
+        impl Clone for Liar<'_> {
+            fn clone(&self) -> Self {
+                Liar {
+                    chunks: self.chunks.clone(),
+                    cloned: true,
+                }
+            }
+        }
+
+        impl<'s> Iterator for Liar<'s> {
+            type Item = &'s [u8];
+
+            fn next(&mut self) -> Option<&'s [u8]> {
+                let chunk = *self.chunks.next()?;
+                Some(if self.cloned { &chunk[..1] } else { chunk })
             }
-            ArenaError::InsufficientMemory => f.write_str("Out of Memory."),
-            ArenaError::ZeroSize => write!(f, "Cannot allocate block of size zero"),
         }
+
+        let mut arena = Arena::with_block_size(64).unwrap();
+        let chunks: [&[u8]; 3] = [&[7; 32], &[8; 32], &[9; 32]];
+        let liar = Liar {
+            chunks: chunks.iter(),
+            cloned: false,
+        };
+
+        assert!(matches!(
+            arena.copy_from_iter_of_slices_exact(liar),
+            Err(ArenaError::LengthMismatch)
+        ));
+        assert!(arena.verify().is_ok());
+        assert_eq!(arena.used(), 3);
     }
-}
 
-impl From<LayoutError> for ArenaError {
-    fn from(_: LayoutError) -> Self {
-        ArenaError::BadAlignment
+    #[test]
+    fn test_grow_last_needs_ptr_inside_block() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        let start = arena.alloc_bytes(8).unwrap().as_mut_ptr();
+
+        assert!(!arena.grow_last(start.wrapping_sub(8), 16, 32));
+        assert_eq!(arena.used(), 8);
+        assert!(arena.grow_last(start, 8, 32));
+        assert_eq!(arena.used(), 32);
     }
-}
 
-impl std::error::Error for ArenaError {}
+    #[test]
+    fn test_copy_from_iter_of_slices() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        let data: Vec<Vec<u32>> = (0..10).map(|i| vec![i; i as usize]).collect();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // `filter` isn't exact and the chunks outgrow the first block
+        let joined = arena
+            .copy_from_iter_of_slices(data.iter().map(Vec::as_slice).filter(|c| !c.is_empty()))
+            .unwrap();
+        let expected: Vec<u32> = data.concat();
+        assert_eq!(joined, expected.as_slice());
+
+        let empty = arena
+            .copy_from_iter_of_slices(std::iter::empty::<&[u8]>())
+            .unwrap();
+        assert!(empty.is_empty());
+    }
 
     #[test]
-    fn test_should_have_low_bits_eq_0() {
-        let size = 32;
-        let mask = size - 1;
+    fn test_copy_from_iter_of_slices_grows_in_place() {
+        let mut arena = Arena::with_block_size(1024).unwrap();
+        let chunk = [7u8; 10];
 
-        let block = Block::new(size).unwrap();
+        let joined = arena
+            .copy_from_iter_of_slices(std::iter::repeat_n(&chunk[..], 5))
+            .unwrap();
+        assert_eq!(joined, &[7; 50]);
+        assert_eq!(arena.used(), 50);
+    }
 
-        // the block address bitwise AND the alignment bits (size - 1) should
-        // be a mutually exclusive set of bits
-        assert!((block.as_ptr() as usize & mask) ^ mask == mask);
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_phase_usage() {
+        let mut arena = Arena::new().unwrap();
+        arena.alloc([0u8; 8]).unwrap();
+
+        arena.phase("parse");
+        arena.alloc([0u8; 100]).unwrap();
+        arena.alloc([0u8; 28]).unwrap();
+
+        arena.phase("lower");
+        arena.alloc([0u8; 64]).unwrap();
+
+        assert_eq!(
+            arena.phase_usage(),
+            [("parse".to_owned(), 128), ("lower".to_owned(), 64)]
+        );
+
+        arena.reset();
+        assert!(arena.phase_usage().is_empty());
+    }
+
+    #[test]
+    fn test_truncate_blocks_runs_released_drops() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        arena.alloc([0u8; 56]).unwrap();
+        arena
+            .alloc_with_drop([DropCounter(drops.clone()), DropCounter(drops.clone())])
+            .unwrap();
+        assert_eq!(arena.blocks.len(), 2);
+
+        arena.truncate_blocks(1);
+        assert_eq!(arena.blocks.len(), 1);
+        assert_eq!(drops.get(), 2);
+        assert_eq!(arena.drops.len(), 1);
+        assert!(arena.verify().is_ok());
+
+        arena.alloc([1u8; 32]).unwrap();
+        drop(arena);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_shrink_to_fit_keeps_used_blocks() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        for _ in 0..3 {
+            arena.alloc([0u8; 64]).unwrap();
+        }
+        arena.reset();
+        arena.alloc([0u8; 8]).unwrap();
+
+        arena.shrink_to_fit();
+        assert_eq!(arena.blocks.len(), 1);
+        assert_eq!(arena.used(), 8);
+    }
+
+    #[test]
+    fn test_rewind_to_released_block_is_rejected() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 16]).unwrap();
+        let snapshot = arena.snapshot();
+        arena.alloc([0u8; 16]).unwrap();
+
+        arena.truncate_blocks(1);
+        let used = arena.used();
+        assert!(matches!(
+            arena.try_rewind_to(snapshot),
+            Err(ArenaError::StaleSnapshot)
+        ));
+        assert_eq!(arena.used(), used);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "released block")]
+    fn test_rewind_to_released_block_panics_in_debug() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 16]).unwrap();
+        let snapshot = arena.snapshot();
+
+        arena.truncate_blocks(1);
+        arena.rewind_to(snapshot);
+    }
+
+    #[test]
+    fn test_alloc_simd_alignments() {
+        #[repr(C, align(16))]
+        struct Lane128([f32; 4]);
+        #[repr(C, align(32))]
+        struct Lane256([f32; 8]);
+        #[repr(C, align(64))]
+        struct Lane512([f32; 16]);
+
+        let mut arena = Arena::with_block_size(256).unwrap();
+        for i in 0..8 {
+            arena.alloc(i as u8).unwrap();
+            let a = arena.alloc(Lane128([1.0; 4])).unwrap() as *mut Lane128;
+            let b = arena.alloc(Lane256([2.0; 8])).unwrap() as *mut Lane256;
+            let c = arena.alloc(Lane512([3.0; 16])).unwrap() as *mut Lane512;
+
+            assert_eq!(a as usize % 16, 0);
+            assert_eq!(b as usize % 32, 0);
+            assert_eq!(c as usize % 64, 0);
+            assert_eq!(unsafe { (*c).0[15] }, 3.0);
+        }
+    }
+
+    #[test]
+    fn test_alloc_over_aligned_gets_aligned_block() {
+        #[repr(C, align(256))]
+        struct Page([u8; 256]);
+
+        let mut arena = Arena::with_block_size(256).unwrap();
+        arena.alloc(1u8).unwrap();
+
+        let page = arena.alloc(Page([9; 256])).unwrap() as *mut Page;
+        assert_eq!(page as usize % 256, 0);
+        assert_eq!(arena.blocks.len(), 2);
+        assert_eq!(arena.capacity(), 512);
+        assert_eq!(arena.blocks[1].get_mut().align, 256);
+    }
+
+    #[test]
+    fn test_alloc_str_from_bytes() {
+        let mut arena = Arena::new().unwrap();
+        assert_eq!(
+            arena.alloc_str_from_bytes("héllo".as_bytes()).unwrap(),
+            "héllo"
+        );
+
+        let used = arena.used();
+        assert!(matches!(
+            arena.alloc_str_from_bytes(b"ok\xffno"),
+            Err(ArenaError::InvalidUtf8 { valid_up_to: 2 })
+        ));
+        assert_eq!(arena.used(), used);
+    }
+
+    #[test]
+    fn test_alloc_over_aligned_near_block_end() {
+        #[repr(align(64))]
+        struct Line([u8; 64]);
+
+        let mut arena = Arena::with_block_size(256).unwrap();
+        arena.alloc([0u8; 3]).unwrap();
+        arena.alloc([0u8; 5]).unwrap();
+        arena.alloc([0u8; 150]).unwrap();
+
+        // 98 bytes are left but the line needs 34 of them for padding, so
+        // it fills the block exactly
+        assert_eq!(arena.remaining(), 98);
+        let line = arena.alloc(Line([1; 64])).unwrap();
+        assert_eq!(line.0[63], 1);
+        assert_eq!(line as *mut Line as usize % 64, 0);
+        assert_eq!(arena.blocks.len(), 1);
+        assert_eq!(arena.remaining(), 0);
+    }
+
+    #[test]
+    fn test_alloc_over_aligned_skips_block_short_after_padding() {
+        #[repr(align(64))]
+        struct Line([u8; 64]);
+
+        let mut arena = Arena::with_block_size(200).unwrap();
+        arena.alloc([0u8; 129]).unwrap();
+
+        // 71 raw bytes remain, but after padding to 192 only 8 do
+        assert_eq!(arena.remaining(), 71);
+        let line = arena.alloc(Line([1; 64])).unwrap();
+        assert_eq!(line.0[0], 1);
+        assert_eq!(line as *mut Line as usize % 64, 0);
+        assert_eq!(arena.blocks.len(), 2);
+
+        // the first block still serves allocations that fit
+        arena.alloc([0u8; 71]).unwrap();
+        assert_eq!(arena.blocks.len(), 2);
+        assert_eq!(arena.blocks[0].get_mut().remaining(), 0);
+    }
+
+    #[test]
+    fn test_truncate_blocks_keeps_snapshot_drops_aligned() {
+        let drops = std::rc::Rc::new(Cell::new(0));
+        let config = ArenaConfig::new().block_size(64).fit(BlockFit::BestFit);
+        let mut arena = Arena::with_config(config).unwrap();
+        arena.alloc([0u8; 40]).unwrap();
+        arena.alloc([0u8; 48]).unwrap();
+
+        // tracked value in block 1, then fill block 1 up
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        arena.alloc([0u8; 8]).unwrap();
+        assert_eq!(arena.blocks[1].get_mut().remaining(), 0);
+
+        arena.alloc([0u8; 8]).unwrap();
+        let snapshot = arena.snapshot();
+        arena.alloc_with_drop(DropCounter(drops.clone())).unwrap();
+        assert_eq!(arena.blocks[0].get_mut().remaining(), 8);
+
+        arena.truncate_blocks(1);
+        assert_eq!(drops.get(), 1);
+
+        arena.try_rewind_to(snapshot).unwrap();
+        assert_eq!(drops.get(), 2);
+        drop(arena);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_shrink_reports_freed_blocks() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        arena.alloc([0u8; 200]).unwrap();
+        arena.alloc([0u8; 64]).unwrap();
+        assert_eq!(arena.capacity(), 64 * 3 + 200);
+
+        arena.reset();
+        arena.alloc([0u8; 8]).unwrap();
+        assert_eq!(
+            arena.shrink_to_fit(),
+            ShrinkResult {
+                freed_bytes: 64 * 2 + 200,
+                freed_blocks: 3,
+            }
+        );
+        assert_eq!(arena.capacity(), 64);
+        assert_eq!(arena.shrink_to_fit(), ShrinkResult::default());
+
+        arena.alloc([0u8; 100]).unwrap();
+        assert_eq!(
+            arena.truncate_blocks(1),
+            ShrinkResult {
+                freed_bytes: 100,
+                freed_blocks: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_as_io_slices_cover_used_bytes() {
+        use std::io::Write;
+
+        let mut arena = Arena::with_block_size(32).unwrap();
+        arena.copy_bytes(b"header;").unwrap();
+        arena.copy_bytes(&[b'x'; 30]).unwrap();
+        arena.copy_bytes(b"tail").unwrap();
+        assert_eq!(arena.blocks.len(), 2);
+
+        let slices = unsafe { arena.as_io_slices() };
+        assert_eq!(slices.len(), 2);
+        assert_eq!(slices.iter().map(|s| s.len()).sum::<usize>(), arena.used());
+
+        let mut out = Vec::new();
+        for slice in &slices {
+            out.write_all(slice).unwrap();
+        }
+        let mut expected = b"header;tail".to_vec();
+        expected.extend_from_slice(&[b'x'; 30]);
+        assert_eq!(out, expected);
+
+        arena.reset();
+        assert!(unsafe { arena.as_io_slices() }.is_empty());
+    }
+
+    #[test]
+    fn test_alloc_shared_from_two_closures() {
+        let arena = Arena::with_block_size(64).unwrap();
+        let left = || arena.alloc_shared(1u32).unwrap();
+        let right = || arena.copy_slice_shared(&[2u64, 3]).unwrap();
+
+        let a = left();
+        let b = right();
+        let c = left();
+        assert_eq!((*a, b, *c), (1, &[2, 3][..], 1));
+
+        let ptrs = [
+            a as *const u32 as usize,
+            b.as_ptr() as usize,
+            c as *const u32 as usize,
+        ];
+        assert!(ptrs[0] != ptrs[1] && ptrs[1] != ptrs[2] && ptrs[0] != ptrs[2]);
+        assert_eq!(ptrs[1] % align_of::<u64>(), 0);
+        assert_eq!(arena.used(), 4 + 4 + 16 + 4);
+    }
+
+    #[test]
+    fn test_alloc_shared_does_not_grow() {
+        let mut arena = Arena::with_block_size(32).unwrap();
+        arena.copy_slice_shared(&[0u8; 32]).unwrap();
+        assert!(matches!(
+            arena.alloc_shared(1u8),
+            Err(ArenaError::InsufficientMemory)
+        ));
+
+        arena.try_reserve(16).unwrap();
+        assert_eq!(*arena.alloc_shared(1u8).unwrap(), 1);
+        assert_eq!(arena.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_shared_values_borrow_earlier_data() {
+        struct Edge<'a> {
+            from: &'a str,
+            to: &'a str,
+            weight: &'a [u32],
+        }
+
+        let mut arena = Arena::with_block_size(128).unwrap();
+        arena.try_reserve(128).unwrap();
+
+        let arena = &arena;
+        let a = arena.alloc_str_shared("a").unwrap();
+        let b = arena.alloc_str_shared("b").unwrap();
+        let weight = arena.copy_slice_shared(&[3, 4]).unwrap();
+        let edges = [
+            arena
+                .alloc_shared(Edge {
+                    from: a,
+                    to: b,
+                    weight,
+                })
+                .unwrap(),
+            arena
+                .alloc_shared(Edge {
+                    from: b,
+                    to: a,
+                    weight: &weight[1..],
+                })
+                .unwrap(),
+        ];
+
+        assert_eq!(
+            (edges[0].from, edges[0].to, edges[0].weight),
+            ("a", "b", &[3, 4][..])
+        );
+        assert_eq!(
+            (edges[1].from, edges[1].to, edges[1].weight),
+            ("b", "a", &[4][..])
+        );
+        assert!(std::ptr::eq(edges[0].from, edges[1].to));
+    }
+
+    #[test]
+    fn test_usage_metrics() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        assert_eq!(arena.block_count(), 1);
+        assert_eq!(arena.reserved_bytes(), 64);
+        assert_eq!(arena.allocated_bytes(), 0);
+        assert_eq!(arena.remaining_bytes(), 64);
+
+        arena.alloc([0u8; 40]).unwrap();
+        arena.alloc([0u8; 100]).unwrap();
+        assert_eq!(arena.block_count(), 2);
+        assert_eq!(arena.reserved_bytes(), 164);
+        assert_eq!(arena.allocated_bytes(), 140);
+        assert_eq!(arena.remaining_bytes(), 0);
+
+        arena.alloc([0u8; 8]).unwrap();
+        assert_eq!(arena.remaining_bytes(), 16);
+    }
+
+    #[test]
+    fn test_system_alloc_count_steady_after_reset() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        assert_eq!(arena.system_alloc_count(), 1);
+
+        let cycle = |arena: &mut Arena| {
+            for _ in 0..4 {
+                arena.alloc([0u8; 48]).unwrap();
+            }
+            arena.alloc([0u8; 200]).unwrap();
+        };
+
+        cycle(&mut arena);
+        let warm = arena.system_alloc_count();
+        assert_eq!(warm, 5);
+
+        arena.reset();
+        cycle(&mut arena);
+        assert_eq!(arena.system_alloc_count(), warm);
+
+        arena.reset();
+        arena.alloc([0u8; 1024]).unwrap();
+        assert_eq!(arena.system_alloc_count(), warm + 1);
+    }
+
+    #[test]
+    fn test_alloc_uninit_slice() {
+        let mut arena = Arena::new().unwrap();
+        arena.alloc(1u8).unwrap();
+
+        let slots = arena.alloc_uninit_slice::<NonNull<u64>>(4).unwrap();
+        assert_eq!(slots.len(), 4);
+        assert_eq!(slots.as_ptr() as usize % align_of::<NonNull<u64>>(), 0);
+        let mut value = 7u64;
+        for slot in slots.iter_mut() {
+            slot.write(NonNull::from(&mut value));
+        }
+
+        let used = arena.used();
+        assert!(arena.alloc_uninit_slice::<String>(0).unwrap().is_empty());
+        assert_eq!(arena.used(), used);
+    }
+
+    #[test]
+    fn test_alloc_slice_fill() {
+        let shared = std::rc::Rc::new(());
+        let mut arena = Arena::new().unwrap();
+
+        assert_eq!(arena.alloc_slice_fill(3, 9u16).unwrap(), &[9, 9, 9]);
+
+        let names = arena.alloc_slice_fill(2, String::from("node")).unwrap();
+        names[1].push('!');
+        assert_eq!(names, ["node", "node!"]);
+
+        // the original `value` is dropped right away, the 5 clones on reset
+        arena.alloc_slice_fill(5, shared.clone()).unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&shared), 6);
+        arena.reset();
+        assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    fn test_alloc_cell_shared_mutation() {
+        let mut arena = Arena::new().unwrap();
+        let node = arena.alloc_cell(1u32).unwrap();
+
+        let (a, b) = (node, node);
+        a.set(a.get() + 1);
+        b.set(b.get() * 10);
+        assert_eq!(node.get(), 20);
+    }
+
+    #[test]
+    fn test_alloc_refcell_drops_value() {
+        let shared = std::rc::Rc::new(());
+        let mut arena = Arena::new().unwrap();
+        let node = arena.alloc_refcell(vec![shared.clone()]).unwrap();
+
+        let (a, b) = (node, node);
+        a.borrow_mut().push(shared.clone());
+        assert_eq!(b.borrow().len(), 2);
+
+        arena.reset();
+        assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    fn test_alloc_str_concat_empty() {
+        let mut arena = Arena::new().unwrap();
+
+        assert_eq!(arena.alloc_str_concat(&[]).unwrap(), "");
+        assert_eq!(arena.alloc_str_concat(&["", ""]).unwrap(), "");
     }
 }
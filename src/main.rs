@@ -5,7 +5,7 @@ use arena::Arena;
 pub fn main() {
     let mut arena = Arena::new().expect("Should construct a new arena");
 
-    arena.alloc_str("wtf");
+    arena.alloc_str("wtf").expect("Should allocate str");
 
     {
         let s: &str = arena.alloc_str("test str").expect("Should allocate str");
@@ -0,0 +1,108 @@
+use crate::{Arena, ArenaError, InlineArenaVec};
+
+/// Nodes stored contiguously in the arena and addressed by `u32` index, so
+/// links between nodes (cycles included) are plain `Copy` integers.
+pub struct NodePool<'a, T> {
+    nodes: InlineArenaVec<'a, T, 0>,
+}
+
+impl<'a, T> NodePool<'a, T> {
+    pub fn new(arena: &'a mut Arena) -> Self {
+        Self {
+            nodes: InlineArenaVec::new(arena),
+        }
+    }
+
+    /// Appends `node`, returning its index.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn push(&mut self, node: T) -> Result<u32, ArenaError> {
+        let index = u32::try_from(self.nodes.len()).map_err(|_| ArenaError::CapacityExceeded)?;
+        self.nodes.push(node)?;
+        Ok(index)
+    }
+
+    #[inline]
+    pub fn get(&self, index: u32) -> Option<&T> {
+        self.nodes.get(index as usize)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        self.nodes.get_mut(index as usize)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        self.nodes.as_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Node {
+        value: char,
+        next: u32,
+    }
+
+    #[test]
+    fn test_node_pool_cycle() {
+        let mut arena = Arena::new().unwrap();
+        let mut pool = NodePool::new(&mut arena);
+
+        let a = pool
+            .push(Node {
+                value: 'a',
+                next: 0,
+            })
+            .unwrap();
+        let b = pool
+            .push(Node {
+                value: 'b',
+                next: a,
+            })
+            .unwrap();
+        let c = pool
+            .push(Node {
+                value: 'c',
+                next: b,
+            })
+            .unwrap();
+        pool.get_mut(a).unwrap().next = c;
+
+        let mut curr = a;
+        let mut visited = String::new();
+        for _ in 0..6 {
+            let node = pool.get(curr).unwrap();
+            visited.push(node.value);
+            curr = node.next;
+        }
+
+        assert_eq!(visited, "acbacb");
+        assert_eq!(pool.len(), 3);
+        assert!(pool.get(3).is_none());
+    }
+
+    #[test]
+    fn test_node_pool_contiguous() {
+        let mut arena = Arena::new().unwrap();
+        let mut pool = NodePool::new(&mut arena);
+
+        for i in 0..100u64 {
+            assert_eq!(pool.push(i).unwrap(), i as u32);
+        }
+
+        assert!(pool.as_slice().iter().copied().eq(0..100));
+    }
+}
@@ -0,0 +1,166 @@
+use std::ops::Deref;
+
+use crate::{Arena, ArenaError, ArenaRef, ArenaSnapshot};
+
+/// Borrows an arena and rewinds it to the point of creation when dropped,
+/// reclaiming everything allocated through the scope.
+///
+/// Unlike `Arena::scope` the scope is a value, so it can be stored. It only
+/// forwards allocation methods: handing out `&mut Arena` would let callers
+/// swap the arena or release blocks from under the snapshot. `by_ref` gives
+/// an `ArenaRef` for allocations that must coexist, and
+/// `InlineArenaVec::in_scope` a temporary collection backed by the scope.
+pub struct ScopedArena<'a> {
+    arena: &'a mut Arena,
+    snapshot: Option<ArenaSnapshot>,
+}
+
+impl<'a> ScopedArena<'a> {
+    pub fn new(arena: &'a mut Arena) -> Self {
+        let snapshot = Some(arena.snapshot());
+        Self { arena, snapshot }
+    }
+
+    /// Nested scope, rewound before this one.
+    #[inline]
+    pub fn scoped(&mut self) -> ScopedArena<'_> {
+        ScopedArena::new(self.arena)
+    }
+
+    #[inline]
+    pub fn by_ref(&mut self) -> ArenaRef<'_> {
+        ArenaRef::new(self.arena)
+    }
+
+    /// For collections that keep the borrow private and never swap or
+    /// release the arena.
+    #[inline]
+    pub(crate) fn arena_mut(&mut self) -> &mut Arena {
+        self.arena
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc<T>(&mut self, obj: T) -> Result<&mut T, ArenaError> {
+        self.arena.alloc(obj)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_with_drop<T: 'static>(&mut self, obj: T) -> Result<&mut T, ArenaError> {
+        self.arena.alloc_with_drop(obj)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_str(&mut self, str: &str) -> Result<&str, ArenaError> {
+        self.arena.alloc_str(str)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn copy_slice<T: Copy>(&mut self, slice: &[T]) -> Result<&mut [T], ArenaError> {
+        self.arena.copy_slice(slice)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_slice_from_fn<T, F>(&mut self, length: usize, f: F) -> Result<&mut [T], ArenaError>
+    where
+        T: 'static,
+        F: FnMut(usize) -> T,
+    {
+        self.arena.alloc_slice_from_fn(length, f)
+    }
+}
+
+impl Deref for ScopedArena<'_> {
+    type Target = Arena;
+
+    fn deref(&self) -> &Arena {
+        self.arena
+    }
+}
+
+impl Drop for ScopedArena<'_> {
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            self.arena.rewind_to(snapshot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InlineArenaVec;
+
+    #[test]
+    fn test_scoped_collection_rewinds_on_drop() {
+        let drops = std::rc::Rc::new(());
+        let mut arena = Arena::with_block_size(256).unwrap();
+        arena.alloc(1u64).unwrap();
+        let used = arena.used();
+
+        {
+            let mut scope = arena.scoped();
+            let mut values = InlineArenaVec::<u32, 4>::in_scope(&mut scope);
+            for i in 0..200 {
+                values.push(i).unwrap();
+            }
+            assert!(values.is_spilled());
+            assert_eq!(values.iter().sum::<u32>(), (0..200).sum());
+            drop(values);
+            assert!(scope.used() > used);
+
+            let nodes = scope.by_ref();
+            let list: Vec<_> = (0..50)
+                .map(|_| nodes.alloc_with_drop(drops.clone()).unwrap())
+                .collect();
+            assert_eq!(list.len(), 50);
+            assert_eq!(std::rc::Rc::strong_count(&drops), 51);
+        }
+
+        assert_eq!(arena.used(), used);
+        assert_eq!(std::rc::Rc::strong_count(&drops), 1);
+    }
+
+    #[test]
+    fn test_scope_in_reused_arena_reclaims_everything() {
+        let mut arena = Arena::with_block_size(64).unwrap();
+        for _ in 0..4 {
+            arena.alloc([0u8; 64]).unwrap();
+        }
+        arena.reset();
+
+        {
+            let mut scope = arena.scoped();
+            let mut values = InlineArenaVec::<u64, 0>::in_scope(&mut scope);
+            for i in 0..6 {
+                values.push(i).unwrap();
+            }
+            drop(values);
+            scope.alloc([0u8; 60]).unwrap();
+        }
+
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    fn test_nested_scopes() {
+        let mut arena = Arena::new().unwrap();
+        let mut outer = arena.scoped();
+        outer.alloc([0u8; 32]).unwrap();
+        let outer_used = outer.used();
+
+        {
+            let mut inner = outer.scoped();
+            inner.alloc([0u8; 64]).unwrap();
+            assert_eq!(inner.used(), outer_used + 64);
+        }
+
+        assert_eq!(outer.used(), outer_used);
+        drop(outer);
+        assert_eq!(arena.used(), 0);
+    }
+}
@@ -0,0 +1,158 @@
+use std::{alloc::Layout, marker::PhantomData, mem::MaybeUninit};
+
+use crate::{Arena, ArenaError, assert_align};
+
+/// Allocation handle for one type, returned by `Arena::typed`.
+///
+/// `T`'s layout and alignment mask are computed once. After the first
+/// allocation picks a block, later ones bump its cursor directly while it
+/// has room instead of going through block selection. Packing matches
+/// `Arena::alloc`.
+pub struct TypedAllocator<'a, T> {
+    arena: &'a mut Arena,
+    layout: Layout,
+    #[cfg(not(any(feature = "stats", feature = "tracing-verbose")))]
+    mask: usize,
+    #[cfg(not(any(feature = "stats", feature = "tracing-verbose")))]
+    warm: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T> TypedAllocator<'a, T> {
+    pub fn new(arena: &'a mut Arena) -> Self {
+        assert_align::<T>();
+        let layout = Layout::new::<T>();
+
+        Self {
+            arena,
+            layout,
+            #[cfg(not(any(feature = "stats", feature = "tracing-verbose")))]
+            mask: layout.align() - 1,
+            #[cfg(not(any(feature = "stats", feature = "tracing-verbose")))]
+            warm: false,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc(&mut self, value: T) -> Result<&mut T, ArenaError> {
+        let ptr = self.alloc_raw()? as *mut T;
+        unsafe {
+            std::ptr::write(ptr, value);
+            Ok(&mut *ptr)
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn alloc_uninit(&mut self) -> Result<&mut MaybeUninit<T>, ArenaError> {
+        let ptr = self.alloc_raw()? as *mut MaybeUninit<T>;
+        Ok(unsafe { &mut *ptr })
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "debug", track_caller)]
+    fn alloc_raw(&mut self) -> Result<*mut u8, ArenaError> {
+        // stats and verbose tracing need the bookkeeping in `try_alloc`
+        #[cfg(not(any(feature = "stats", feature = "tracing-verbose")))]
+        if self.warm
+            && let Some(ptr) = self.arena.bump_active(self.layout.size(), self.mask)
+        {
+            return Ok(ptr);
+        }
+
+        let ptr = self.arena.try_alloc(self.layout)?;
+        // `try_alloc` leaves the block it picked active. Nothing else touches
+        // the arena while `self` borrows it, so the other blocks only lose
+        // room and the fit strategy keeps picking that block while it fits.
+        #[cfg(not(any(feature = "stats", feature = "tracing-verbose")))]
+        {
+            self.warm = true;
+        }
+        Ok(ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArenaConfig, BlockFit};
+
+    #[test]
+    fn test_typed_matches_generic_packing() {
+        let mut generic = Arena::with_block_size(512).unwrap();
+        let mut typed = Arena::with_block_size(512).unwrap();
+        generic.alloc(1u8).unwrap();
+        typed.alloc(1u8).unwrap();
+
+        let mut generic_offsets = Vec::new();
+        let mut typed_offsets = Vec::new();
+        for i in 0..40u64 {
+            generic_offsets.push(generic.alloc(i).unwrap() as *mut u64 as usize);
+        }
+
+        let mut alloc = typed.typed::<u64>();
+        for i in 0..40u64 {
+            let value = alloc.alloc(i).unwrap();
+            assert_eq!(*value, i);
+            typed_offsets.push(value as *mut u64 as usize);
+        }
+
+        assert_eq!(generic.used(), typed.used());
+        assert_eq!(generic.blocks.len(), typed.blocks.len());
+
+        // all 40 values fit in the first block, so offsets from its start
+        // must line up
+        let base = |arena: &mut Arena, offsets: &[usize]| {
+            let start = arena.blocks[0].get_mut().as_ptr() as usize;
+            offsets.iter().map(|ptr| ptr - start).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            base(&mut generic, &generic_offsets),
+            base(&mut typed, &typed_offsets)
+        );
+        assert!(typed_offsets.iter().all(|ptr| ptr % align_of::<u64>() == 0));
+    }
+
+    #[test]
+    fn test_typed_matches_generic_across_blocks() {
+        for fit in [BlockFit::FirstFit, BlockFit::BestFit] {
+            let arena = || {
+                let config = ArenaConfig::new().block_size(64).fit(fit);
+                let mut arena = Arena::with_config(config).unwrap();
+                arena.alloc([0u8; 48]).unwrap();
+                arena.alloc([0u8; 56]).unwrap();
+                arena
+            };
+
+            let mut generic = arena();
+            for i in 0..3u32 {
+                generic.alloc(i).unwrap();
+            }
+
+            let mut typed = arena();
+            let mut alloc = typed.typed::<u32>();
+            for i in 0..3u32 {
+                alloc.alloc(i).unwrap();
+            }
+
+            assert_eq!(generic.block_utilization(), typed.block_utilization());
+        }
+    }
+
+    #[test]
+    fn test_typed_alloc_uninit() {
+        #[repr(align(32))]
+        struct Wide([u8; 32]);
+
+        let mut arena = Arena::new().unwrap();
+        arena.alloc(1u8).unwrap();
+
+        let mut alloc = arena.typed::<Wide>();
+        let slot = alloc.alloc_uninit().unwrap();
+        assert_eq!(slot.as_ptr() as usize % 32, 0);
+        let wide = slot.write(Wide([5; 32]));
+        assert_eq!(wide.0[31], 5);
+    }
+}
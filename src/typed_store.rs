@@ -0,0 +1,127 @@
+use std::{any::TypeId, collections::HashMap, ptr::NonNull};
+
+use crate::{Arena, ArenaConfig, ArenaError};
+
+/// Store keeping every `T` in its own block chain, so all values of one
+/// type sit next to each other.
+pub struct TypedArena {
+    regions: HashMap<TypeId, Region>,
+    config: ArenaConfig,
+}
+
+struct Region {
+    arena: Arena,
+
+    /// contiguous runs of values as (start, count)
+    chunks: Vec<(NonNull<u8>, usize)>,
+}
+
+impl TypedArena {
+    pub fn new() -> Self {
+        Self::with_config(ArenaConfig::default())
+    }
+
+    pub fn with_config(config: ArenaConfig) -> Self {
+        Self {
+            regions: HashMap::new(),
+            config,
+        }
+    }
+
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn push<T: 'static>(&mut self, value: T) -> Result<&mut T, ArenaError> {
+        let region = match self.regions.entry(TypeId::of::<T>()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => entry.insert(Region {
+                arena: Arena::with_config(self.config)?,
+                chunks: Vec::new(),
+            }),
+        };
+
+        let ptr = region.arena.alloc_with_drop(value)? as *mut T;
+        let start = ptr as *mut u8;
+
+        match region.chunks.last_mut() {
+            Some((chunk, count))
+                if chunk.as_ptr().wrapping_add(*count * size_of::<T>()) == start =>
+            {
+                *count += 1;
+            }
+            _ => region
+                .chunks
+                .push((unsafe { NonNull::new_unchecked(start) }, 1)),
+        }
+
+        Ok(unsafe { &mut *ptr })
+    }
+
+    pub fn count<T: 'static>(&self) -> usize {
+        self.regions.get(&TypeId::of::<T>()).map_or(0, |region| {
+            region.chunks.iter().map(|(_, count)| count).sum()
+        })
+    }
+
+    /// Slices of consecutive `T`s, one per block the region spans.
+    pub fn chunks<T: 'static>(&self) -> impl Iterator<Item = &[T]> {
+        self.regions
+            .get(&TypeId::of::<T>())
+            .into_iter()
+            .flat_map(|region| region.chunks.iter())
+            .map(|(ptr, count)| unsafe {
+                std::slice::from_raw_parts(ptr.as_ptr() as *const T, *count)
+            })
+    }
+
+    pub fn iter<T: 'static>(&self) -> impl Iterator<Item = &T> {
+        self.chunks::<T>().flatten()
+    }
+}
+
+impl Default for TypedArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f32, f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    #[test]
+    fn test_typed_arena_iterates_each_type_contiguously() {
+        let mut store = TypedArena::new();
+
+        for i in 0..4 {
+            store.push(Position(i as f32, 0.0)).unwrap();
+            store.push(Health(i)).unwrap();
+        }
+
+        assert_eq!(store.count::<Position>(), 4);
+        assert_eq!(store.count::<Health>(), 4);
+        assert_eq!(store.count::<u8>(), 0);
+
+        let positions: Vec<_> = store.chunks::<Position>().collect();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0][3], Position(3.0, 0.0));
+
+        assert!(store.iter::<Health>().map(|h| h.0).eq(0..4));
+    }
+
+    #[test]
+    fn test_typed_arena_spans_blocks() {
+        let mut store = TypedArena::with_config(ArenaConfig::new().block_size(64));
+
+        for i in 0..40u64 {
+            store.push(i).unwrap();
+        }
+
+        assert!(store.chunks::<u64>().count() > 1);
+        assert!(store.iter::<u64>().copied().eq(0..40));
+    }
+}